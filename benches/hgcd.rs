@@ -0,0 +1,86 @@
+use std::time::{Duration, Instant};
+
+use ark_guo::{
+    integer::{hgcd::partial_xgcd_with_base_bits, ZZ},
+    Integer,
+};
+use ark_std::{rand::Rng, test_rng, Zero};
+
+const SAMPLES: usize = 21;
+
+// Random integer with exactly `bits` bits
+fn random_bits<R: Rng>(bits: u32, rng: &mut R) -> ZZ {
+    let limb = ZZ::from(1u128 << 64);
+    let mut e = ZZ::from(1u8);
+    for _ in 0..bits.div_ceil(64) {
+        e = e * &limb + ZZ::from(rng.gen_range(0..u64::MAX));
+    }
+    e >> (bits.div_ceil(64) * 64 + 1 - bits)
+}
+
+// Single division steps until a <= bound, as NUCOMP and NUDUPL run them below the threshold
+fn classical(a: &ZZ, b: &ZZ, bound: &ZZ) -> usize {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    let (mut x, mut y) = (ZZ::from(1), ZZ::from(0));
+    let (mut q, mut r) = (ZZ::from(0), ZZ::from(0));
+    let mut z = 0;
+    while a > *bound && !b.is_zero() {
+        q.clone_from(&a);
+        r.clone_from(&b);
+        q.div_rem_floor_mut(&mut r);
+        std::mem::swap(&mut a, &mut b);
+        std::mem::swap(&mut b, &mut r);
+        y.sub_mul(&q, &x);
+        std::mem::swap(&mut x, &mut y);
+        z += 1;
+    }
+    z
+}
+
+// Median, which is less sensitive than the mean to the odd slow run
+fn median(times: impl Iterator<Item = Duration>) -> Duration {
+    let mut times: Vec<Duration> = times.collect();
+    times.sort();
+    times[times.len() / 2]
+}
+
+// Median time of the partial Euclid of NUCOMP, from n bits down to n / 2 bits, with single division
+// steps, with Lehmer steps only, and with half gcds recursing down to HGCD_BASE_BITS and to a few
+// other base sizes. Single steps against the other two gives ClassConfig::half_gcd_threshold_bits,
+// Lehmer against the half gcds and the base sizes against each other HGCD_BASE_BITS.
+fn main() {
+    let rng = &mut test_rng();
+
+    for bits in [256u32, 512, 768, 1024, 1536, 2048, 3000, 4096, 6000, 8192, 12000, 16384, 32768] {
+        let inputs: Vec<(ZZ, ZZ, ZZ)> = (0..SAMPLES)
+            .map(|_| {
+                let a = random_bits(bits, rng);
+                let b = random_bits(bits - 1, rng);
+                (a, b, random_bits(bits / 2, rng))
+            })
+            .collect();
+
+        let single = median(inputs.iter().map(|(a, b, bound)| {
+            let start = Instant::now();
+            classical(a, b, bound);
+            start.elapsed()
+        }));
+        let half = median(inputs.iter().map(|(a, b, bound)| {
+            let start = Instant::now();
+            a.partial_xgcd(b, bound);
+            start.elapsed()
+        }));
+        let [lehmer, base512, base2048, base4096] = [u32::MAX, 512, 2048, 4096].map(|base_bits| {
+            median(inputs.iter().map(|(a, b, bound)| {
+                let start = Instant::now();
+                partial_xgcd_with_base_bits(a, b, bound, base_bits);
+                start.elapsed()
+            }))
+        });
+
+        println!(
+            "partial euclid {bits:>5} bits: single steps {single:?}, lehmer {lehmer:?}, half gcd {half:?}, \
+             base 512 {base512:?}, base 2048 {base2048:?}, base 4096 {base4096:?}"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use ark_guo::{
+    class::{
+        config::{ClassConfig, FormConfig, TestClassConfig},
+        context::ClassGroupContext,
+        ClassGroup,
+    },
+    integer::ZZ,
+    Integer,
+};
+use ark_std::{test_rng, UniformRand};
+
+const SAMPLES: usize = 101;
+
+// Config with D = -p for the first prime p > 2^k with p = 3 mod 4, and the NUCOMP bound
+// 2^((k - 2) / 4) ~ |D/4|^(1/4)
+macro_rules! prime_config {
+    ($name:ident, $k:expr) => {
+        #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
+        struct $name;
+
+        impl FormConfig for $name {
+            type Int = ZZ;
+        }
+
+        impl ClassConfig for $name {
+            fn discriminant() -> Self::Int {
+                static DISCRIMINANT: OnceLock<ZZ> = OnceLock::new();
+                DISCRIMINANT
+                    .get_or_init(|| {
+                        let mut p = ZZ::from(2).pow(&$k) + ZZ::from(3);
+                        while !p.is_probably_prime(30) {
+                            p += ZZ::from(4);
+                        }
+                        -p
+                    })
+                    .clone()
+            }
+
+            fn default_nucomp_bound() -> Self::Int {
+                ZZ::from(2).pow(&(($k - 2) / 4))
+            }
+        }
+    };
+}
+
+prime_config!(Disc512, 512u32);
+prime_config!(Disc1024, 1024u32);
+prime_config!(Disc2048, 2048u32);
+
+// Median, which is less sensitive than the mean to the odd slow run
+fn median(times: impl Iterator<Item = Duration>) -> Duration {
+    let mut times: Vec<Duration> = times.collect();
+    times.sort();
+    times[times.len() / 2]
+}
+
+// Median time of a cube with NUCUBE and with NUDUPL followed by NUCOMP, on random elements
+fn bench<T: ClassConfig<Int = ZZ>>() {
+    let rng = &mut test_rng();
+    let forms: Vec<ClassGroup<T>> = (0..SAMPLES).map(|_| ClassGroup::rand(rng)).collect();
+    let ctx = &mut ClassGroupContext::new();
+    let (mut r, mut sq) = (ClassGroup::default(), ClassGroup::default());
+
+    let nucube = median(forms.iter().map(|f| {
+        let start = Instant::now();
+        ClassGroup::nucube_into(ctx, &mut r, f);
+        start.elapsed()
+    }));
+    let separate = median(forms.iter().map(|f| {
+        let start = Instant::now();
+        ClassGroup::nudupl_into(ctx, &mut sq, f);
+        ClassGroup::nucomp_into(ctx, &mut r, &sq, f);
+        start.elapsed()
+    }));
+
+    let bits = T::discriminant().significant_bits();
+    println!("cube, D {bits:>4} bits: nucube {nucube:?}, nudupl + nucomp {separate:?}");
+}
+
+fn main() {
+    bench::<Disc512>();
+    bench::<Disc1024>();
+    bench::<Disc2048>();
+    bench::<TestClassConfig>();
+}
@@ -0,0 +1,98 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use ark_guo::{
+    class::{
+        config::{ClassConfig, FormConfig, TestClassConfig},
+        context::ClassGroupContext,
+        ClassGroup, PowStrategy,
+    },
+    integer::ZZ,
+    Integer,
+};
+use ark_std::{rand::Rng, test_rng, UniformRand};
+
+const SAMPLES: usize = 11;
+
+// Config with D = -p for the first prime p > 2^k with p = 3 mod 4, and the NUCOMP bound
+// 2^((k - 2) / 4) ~ |D/4|^(1/4)
+macro_rules! prime_config {
+    ($name:ident, $k:expr) => {
+        #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
+        struct $name;
+
+        impl FormConfig for $name {
+            type Int = ZZ;
+        }
+
+        impl ClassConfig for $name {
+            fn discriminant() -> Self::Int {
+                static DISCRIMINANT: OnceLock<ZZ> = OnceLock::new();
+                DISCRIMINANT
+                    .get_or_init(|| {
+                        let mut p = ZZ::from(2).pow(&$k) + ZZ::from(3);
+                        while !p.is_probably_prime(30) {
+                            p += ZZ::from(4);
+                        }
+                        -p
+                    })
+                    .clone()
+            }
+
+            fn default_nucomp_bound() -> Self::Int {
+                ZZ::from(2).pow(&(($k - 2) / 4))
+            }
+        }
+    };
+}
+
+prime_config!(Disc512, 512u32);
+prime_config!(Disc1024, 1024u32);
+
+// Random exponent with exactly `bits` bits
+fn random_exponent<R: Rng>(bits: u32, rng: &mut R) -> ZZ {
+    let limb = ZZ::from(1u128 << 64);
+    let mut e = ZZ::from(1u8);
+    for _ in 0..bits.div_ceil(64) {
+        e = e * &limb + ZZ::from(rng.gen_range(0..u64::MAX));
+    }
+    e >> (bits.div_ceil(64) * 64 + 1 - bits)
+}
+
+// Median, which is less sensitive than the mean to the odd slow run
+fn median(times: impl Iterator<Item = Duration>) -> Duration {
+    let mut times: Vec<Duration> = times.collect();
+    times.sort();
+    times[times.len() / 2]
+}
+
+// Median time of NUPOW per strategy on random bases, for exponent sizes from a few words up to the
+// ones used in practice, with DoubleBase relative to Wnaf, below 1 where it wins
+fn bench<T: ClassConfig<Int = ZZ>>() {
+    let rng = &mut test_rng();
+    let ctx = &mut ClassGroupContext::new();
+    let mut r = ClassGroup::default();
+    let dbits = T::discriminant().significant_bits();
+
+    for bits in [64u32, 128, 256, 512, 1024, 2048, 3000] {
+        let inputs: Vec<(ClassGroup<T>, ZZ)> =
+            (0..SAMPLES).map(|_| (ClassGroup::rand(rng), random_exponent(bits, rng))).collect();
+        let [wnaf, double_base] = [PowStrategy::Wnaf, PowStrategy::DoubleBase].map(|strategy| {
+            median(inputs.iter().map(|(f, e)| {
+                let start = Instant::now();
+                ClassGroup::nupow_with_into(ctx, &mut r, f, e, strategy);
+                start.elapsed()
+            }))
+        });
+        let ratio = double_base.as_secs_f64() / wnaf.as_secs_f64();
+        println!("nupow, D {dbits:>4} bits, n {bits:>4} bits: Wnaf {wnaf:?}, DoubleBase {double_base:?} ({ratio:.3})");
+    }
+}
+
+fn main() {
+    bench::<Disc512>();
+    bench::<Disc1024>();
+    bench::<TestClassConfig>();
+}
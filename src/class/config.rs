@@ -1,32 +1,120 @@
 use serde::{Deserialize, Serialize};
+use ark_std::{fmt::Debug, hash::Hash};
 
 use crate::{
+    class::{
+        hash::HASH_PRIME_BITS,
+        number::{self, ClassNumberError},
+        sample::{small_prime_forms, SamplingStrategy, SAMPLING_MARGIN_BITS},
+        structure::{ClassGroupStructure, StructureError},
+        ClassGroup,
+    },
     integer::ZZ,
     Integer,
 };
 
-pub trait ClassConfig: Send + Sync + Sized + 'static {
+// Bit size of a above which NUCOMP and NUDUPL run their partial Euclid through half gcds, which
+// allocate. The half gcds are ahead of single division steps from about 768 bits in benches/hgcd.rs,
+// but the default keeps discriminants up to 4096 bits, TestClassConfig included, allocation-free.
+pub const HALF_GCD_THRESHOLD_BITS: u32 = 2048;
+
+// Bits the a and b of an UnreducedForm may grow past sqrt|D| by default. NUCOMP and NUDUPL with Atkin's
+// partial reduction leave them within a few bits of sqrt|D|, and some tens of bits above in long chains.
+pub const UNREDUCED_SLACK_BITS: u32 = 32;
+
+// Coefficient type of the forms of a ClassGroup, all the core arithmetic (reduction, NUCOMP, NUDUPL,
+// NUCUBE, NUPOW on a ClassGroupContext) needs. Forms whose discriminant is only known at runtime, see
+// class::number, implement only this and get the NUCOMP bound from ClassGroupContext::with_bound.
+pub trait FormConfig:
+    Copy + Clone + Debug + Default + Eq + Ord + Hash + Send + Sync + Sized + 'static
+{
     type Int: Integer;
+}
+
+pub trait ClassConfig: FormConfig {
+    // Prime whose prime form is the generator, None for the smallest prime that splits
+    const GENERATOR_PRIME: Option<u64> = None;
     
     fn discriminant() -> Self::Int;
     fn default_nucomp_bound() -> Self::Int;
+
+    // Agreed-upon base element: the reduced prime form of GENERATOR_PRIME, by default of the
+    // smallest prime p >= 2 with (D / p) = 1, see ClassGroup::prime_form
+    fn generator() -> ClassGroup<Self> {
+        match Self::GENERATOR_PRIME {
+            Some(p) => ClassGroup::prime_form(Self::Int::from(p)).expect("GENERATOR_PRIME must be a split prime"),
+            None => small_prime_forms::<Self>(1).remove(0),
+        }
+    }
+
+    // Class number h(D) of the discriminant, see class::number::class_number. Where the exponent of the
+    // class group cannot single out h(D), or the search interval is too wide, it is the product of the
+    // invariants of ClassGroupStructure, with verify so that its factor base generates Cl(D) under GRH.
+    fn class_number() -> Result<ZZ, ClassNumberError>
+    where
+        Self: ClassConfig<Int = ZZ>,
+    {
+        match number::class_number(&Self::discriminant()) {
+            Err(ClassNumberError::ExponentTooSmall | ClassNumberError::IntervalTooWide) => {
+                // A default factor base of mostly ramified primes can miss part of the group, then it grows
+                // to twice the prime verify found outside it, which is below Bach's bound
+                let mut structure = ClassGroupStructure::<Self>::compute(true);
+                while let Err(StructureError::NotGenerated(p)) = structure {
+                    structure = ClassGroupStructure::<Self>::compute_with_bound(2 * u64::from(p), true);
+                }
+                Ok(structure.map_err(ClassNumberError::Structure)?.class_number())
+            }
+            h => h,
+        }
+    }
+
+    // Bit size of a or b above which an UnreducedForm gets fully reduced, by default
+    // UNREDUCED_SLACK_BITS above the size of sqrt|D|
+    fn unreduced_bound_bits() -> u32 {
+        Self::discriminant().significant_bits().div_ceil(2) + UNREDUCED_SLACK_BITS
+    }
+
+    // Bit size of a above which NUCOMP and NUDUPL run their partial Euclid through half gcds, see
+    // HALF_GCD_THRESHOLD_BITS
+    fn half_gcd_threshold_bits() -> u32 {
+        HALF_GCD_THRESHOLD_BITS
+    }
+
+    // Bit size of the random exponents of the sampler. The default covers the class number bound
+    // h < sqrt(|D|) ln|D| with SAMPLING_MARGIN_BITS to spare
+    fn sampling_exponent_bits() -> u32 {
+        let bits = Self::discriminant().significant_bits();
+        bits / 2 + bits.ilog2() + 1 + SAMPLING_MARGIN_BITS
+    }
+
+    // How UniformRand draws elements
+    fn sampling_strategy() -> SamplingStrategy {
+        SamplingStrategy::default()
+    }
+
+    // Bit size of the primes ClassGroup::hash_to_group maps to
+    fn hash_prime_bits() -> u32 {
+        HASH_PRIME_BITS
+    }
 }
 
 /// Specs for testing class group - discriminant and nucomp bound
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct TestClassConfig;
 
-impl ClassConfig for TestClassConfig {
+impl FormConfig for TestClassConfig {
     type Int = ZZ;
+}
 
+impl ClassConfig for TestClassConfig {
     fn discriminant() -> Self::Int {
-        const DISCRIMINANT_ABS: &'static str = "4317691815857125654877103063805538258304534055913558995937896265414226467675838217967464111823805637397681689548933380287963403903024070698952698713564600001020302455551579541375030138161381752127687987056044417286485558370006218644954314456098141716563457356544700187694292714939997529330586989685677713138404829982972959463316478999491007128334186598645083406504058406930082094275532391885408037130383664772950500667176010578495338475449244935691537254958874025748466115437469954409625202941555052249642870792934493218898979574520791693575851109711772583447666625631816079241989890465235537359511278431697448098774126877921343260401146554658952117359789054272489217677591785658403131142050404745495763330191385022052496621052299890443575489742515595497931145259287339142099544687065613866268583963983615742155847383688681319437071932009650168538096026066985509257232980837633229313525898394539299377018819178943577932583951";
+        const DISCRIMINANT_ABS: &str = "4317691815857125654877103063805538258304534055913558995937896265414226467675838217967464111823805637397681689548933380287963403903024070698952698713564600001020302455551579541375030138161381752127687987056044417286485558370006218644954314456098141716563457356544700187694292714939997529330586989685677713138404829982972959463316478999491007128334186598645083406504058406930082094275532391885408037130383664772950500667176010578495338475449244935691537254958874025748466115437469954409625202941555052249642870792934493218898979574520791693575851109711772583447666625631816079241989890465235537359511278431697448098774126877921343260401146554658952117359789054272489217677591785658403131142050404745495763330191385022052496621052299890443575489742515595497931145259287339142099544687065613866268583963983615742155847383688681319437071932009650168538096026066985509257232980837633229313525898394539299377018819178943577932583951";
         
         - Self::Int::from(DISCRIMINANT_ABS)
     }
 
-    fn default_nucomp_bound() -> <Self as ClassConfig>::Int {
-        const DEFAULT_NUCOMP_BOUND: &'static str = "1441494235883924393135121185924968992098742588002850717276862615869494967768610172806474508038126507301623260102283148459650419699318656372397440990724413774107186866776020521095301045131635882584986282478633417210136028312986731859";
+    fn default_nucomp_bound() -> Self::Int {
+        const DEFAULT_NUCOMP_BOUND: &str = "1441494235883924393135121185924968992098742588002850717276862615869494967768610172806474508038126507301623260102283148459650419699318656372397440990724413774107186866776020521095301045131635882584986282478633417210136028312986731859";
 
         Self::Int::from(DEFAULT_NUCOMP_BOUND)
     }
@@ -0,0 +1,143 @@
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+};
+
+use zeroize::Zeroize;
+
+use crate::class::config::{ClassConfig, FormConfig, HALF_GCD_THRESHOLD_BITS};
+
+thread_local! {
+    // Contexts of with_thread_context, one per config
+    static CONTEXTS: RefCell<HashMap<TypeId, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+// Scratch space for the in-place core of ClassGroup (nucomp_into, nudupl_into, reduce_in_place),
+// along with the NUCOMP bound and half gcd threshold of the discriminant the core runs on.
+// The temporaries keep their allocations between calls, so once they have grown to the size of the
+// discriminant, composing, squaring and reducing do not allocate anymore, except for the half gcds above
+// the threshold.
+// A context is tied to a discriminant but not to any form, and can be reused freely, but not shared
+// between threads.
+#[derive(Clone, Debug)]
+pub struct ClassGroupContext<T: FormConfig> {
+    // NUCOMP bound |D/4|^(1/4) and ClassConfig::half_gcd_threshold_bits
+    pub(super) bound: T::Int,
+    pub(super) half_gcd_threshold_bits: u32,
+
+    // Named after the quantities of NUCOMP and NUDUPL they hold
+    pub(super) s: T::Int,
+    pub(super) m: T::Int,
+    pub(super) d: T::Int,
+    pub(super) f: T::Int,
+    pub(super) g: T::Int,
+    pub(super) h: T::Int,
+    pub(super) l: T::Int,
+    pub(super) u: T::Int,
+    pub(super) v: T::Int,
+    pub(super) w: T::Int,
+    pub(super) x: T::Int,
+    pub(super) y: T::Int,
+    pub(super) a1g: T::Int,
+    pub(super) a2g: T::Int,
+    pub(super) sg: T::Int,
+    pub(super) bx: T::Int,
+    pub(super) by: T::Int,
+    pub(super) cx: T::Int,
+    pub(super) cy: T::Int,
+    pub(super) dx: T::Int,
+    pub(super) dy: T::Int,
+    pub(super) ua: T::Int,
+    pub(super) wc: T::Int,
+    pub(super) q1: T::Int,
+    pub(super) q2: T::Int,
+    pub(super) q3: T::Int,
+    pub(super) q4: T::Int,
+
+    // Quotient and remainder of single division steps
+    pub(super) q: T::Int,
+    pub(super) t: T::Int,
+}
+
+impl<T: ClassConfig> ClassGroupContext<T> {
+    // Empty context for the discriminant of T, temporaries are allocated lazily
+    pub fn new() -> Self {
+        let mut ctx = Self::with_bound(T::default_nucomp_bound());
+        ctx.half_gcd_threshold_bits = T::half_gcd_threshold_bits();
+        ctx
+    }
+}
+
+impl<T: ClassConfig> Default for ClassGroupContext<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: FormConfig> ClassGroupContext<T> {
+    // Empty context for forms of a discriminant with NUCOMP bound `bound`, which T does not know,
+    // with the default half gcd threshold
+    pub(crate) fn with_bound(bound: T::Int) -> Self {
+        let z = T::Int::default;
+        Self {
+            bound,
+            half_gcd_threshold_bits: HALF_GCD_THRESHOLD_BITS,
+            s: z(), m: z(), d: z(), f: z(), g: z(), h: z(), l: z(), u: z(), v: z(), w: z(), x: z(), y: z(),
+            a1g: z(), a2g: z(), sg: z(), bx: z(), by: z(), cx: z(), cy: z(), dx: z(), dy: z(), ua: z(), wc: z(),
+            q1: z(), q2: z(), q3: z(), q4: z(), q: z(), t: z(),
+        }
+    }
+}
+
+impl<T: FormConfig> ClassGroupContext<T> {
+    // The temporaries, e.g. to check that zeroize clears their buffers
+    pub fn temporaries(&self) -> [&T::Int; 29] {
+        let Self {
+            bound: _,
+            half_gcd_threshold_bits: _,
+            s, m, d, f, g, h, l, u, v, w, x, y,
+            a1g, a2g, sg, bx, by, cx, cy, dx, dy, ua, wc,
+            q1, q2, q3, q4, q, t,
+        } = self;
+        [
+            s, m, d, f, g, h, l, u, v, w, x, y,
+            a1g, a2g, sg, bx, by, cx, cy, dx, dy, ua, wc,
+            q1, q2, q3, q4, q, t,
+        ]
+    }
+}
+
+// op on the context of this thread for T, created on first use, which the methods of ClassGroup and
+// its operators that take no context run on. The context is taken out of the thread-local map while op
+// runs, so a nested call gets a fresh one instead of a second borrow.
+pub(crate) fn with_thread_context<T: ClassConfig, R>(op: impl FnOnce(&mut ClassGroupContext<T>) -> R) -> R {
+    let id = TypeId::of::<T>();
+    let mut ctx = CONTEXTS
+        .with(|c| c.borrow_mut().remove(&id))
+        .and_then(|c| c.downcast::<ClassGroupContext<T>>().ok())
+        .unwrap_or_else(|| Box::new(ClassGroupContext::new()));
+    let r = op(&mut ctx);
+    CONTEXTS.with(|c| c.borrow_mut().insert(id, ctx));
+    r
+}
+
+// The temporaries hold intermediate values of whatever was computed last, e.g. of pow_secret
+impl<T: FormConfig> Zeroize for ClassGroupContext<T> {
+    fn zeroize(&mut self) {
+        let Self {
+            bound: _,
+            half_gcd_threshold_bits: _,
+            s, m, d, f, g, h, l, u, v, w, x, y,
+            a1g, a2g, sg, bx, by, cx, cy, dx, dy, ua, wc,
+            q1, q2, q3, q4, q, t,
+        } = self;
+        for z in [
+            s, m, d, f, g, h, l, u, v, w, x, y,
+            a1g, a2g, sg, bx, by, cx, cy, dx, dy, ua, wc,
+            q1, q2, q3, q4, q, t,
+        ] {
+            z.zeroize();
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, SerializationError, Valid, Validate,
+};
+use ark_std::Zero;
+
+use crate::{
+    class::{config::ClassConfig, context::ClassGroupContext, ClassGroup, ClassGroupError},
+    msm::signed_digits,
+    AdditiveGroup, Integer,
+};
+
+// Fixed-base window table for a generator g
+// row k holds g^(j * 2^(k * window)) for j = 1..=2^(window - 1), so g^e only needs one
+// composition per window of e and no squarings. Digits are signed, negative ones use the free inverse.
+// The fields are private so that the table keeps the shape mul indexes by, see Valid.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, CanonicalSerialize)]
+pub struct FixedBaseTable<T: ClassConfig> {
+    window: u32,
+    max_bits: u32,
+    table: Vec<Vec<ClassGroup<T>>>,
+}
+
+impl<T: ClassConfig> FixedBaseTable<T> {
+    // Precompute the table for exponents of at most max_bits bits, or InvalidWindow if window is not
+    // in 1..32 or the number of windows overflows
+    pub fn new(g: &ClassGroup<T>, max_bits: u32, window: u32) -> Result<Self, ClassGroupError> {
+        let (num_windows, row_len) = Self::dimensions(max_bits, window).ok_or(ClassGroupError::InvalidWindow)?;
+
+        let ctx = &mut ClassGroupContext::new();
+        let mut table = Vec::with_capacity(num_windows as usize);
+        let mut base = g.clone();
+        for _ in 0..num_windows {
+            // base, base^2, ..., base^(2^(window - 1))
+            let mut row = Vec::with_capacity(row_len);
+            row.push(base.clone());
+            for j in 1..row_len {
+                let mut t = ClassGroup::default();
+                if j == 1 {
+                    ClassGroup::nudupl_into(ctx, &mut t, &base);
+                } else {
+                    ClassGroup::nucomp_into(ctx, &mut t, &row[j - 1], &base);
+                }
+                row.push(t);
+            }
+            table.push(row);
+
+            // base <- base^(2^window)
+            for _ in 0..window {
+                let mut t = ClassGroup::default();
+                ClassGroup::nudupl_into(ctx, &mut t, &base);
+                base = t;
+            }
+        }
+
+        Ok(Self { window, max_bits, table })
+    }
+
+    // Bits of the exponent per row
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    // Largest bit size of the exponents of mul
+    pub fn max_bits(&self) -> u32 {
+        self.max_bits
+    }
+
+    // Row k holds g^(j * 2^(k * window)) for j = 1..=2^(window - 1)
+    pub fn table(&self) -> &[Vec<ClassGroup<T>>] {
+        &self.table
+    }
+
+    // Number of rows and length of a row of the table, None if window is not in 1..32 or the number of
+    // rows overflows
+    fn dimensions(max_bits: u32, window: u32) -> Option<(u32, usize)> {
+        if !(1..32).contains(&window) {
+            return None;
+        }
+        // One extra window absorbs the carry of the signed recoding
+        Some((max_bits.div_ceil(window).checked_add(1)?, 1usize << (window - 1)))
+    }
+
+    // g^e, or ExponentTooLarge if |e| has more than max_bits bits
+    pub fn mul(&self, e: &T::Int) -> Result<ClassGroup<T>, ClassGroupError> {
+        if e.significant_bits() > self.max_bits {
+            return Err(ClassGroupError::ExponentTooLarge);
+        }
+
+        let ctx = &mut ClassGroupContext::new();
+        let mut r = ClassGroup::zero();
+        let mut t = ClassGroup::default();
+        for (k, d) in signed_digits(&e.abs(), self.window, self.table.len()).into_iter().enumerate() {
+            if d > 0 {
+                ClassGroup::nucomp_into(ctx, &mut t, &r, &self.table[k][d as usize - 1]);
+                std::mem::swap(&mut r, &mut t);
+            } else if d < 0 {
+                ClassGroup::nucomp_into(ctx, &mut t, &r, &-&self.table[k][(-d) as usize - 1]);
+                std::mem::swap(&mut r, &mut t);
+            }
+        }
+
+        if *e < T::Int::zero() {
+            r.b.neg_in_place();
+            r.reduce_in_place(ctx);
+        }
+        Ok(r)
+    }
+
+    // g^e for every e in es
+    pub fn batch_mul(&self, es: &[T::Int]) -> Result<Vec<ClassGroup<T>>, ClassGroupError> {
+        es.iter().map(|e| self.mul(e)).collect()
+    }
+}
+
+impl<T: ClassConfig> CanonicalDeserialize for FixedBaseTable<T> {
+    fn deserialize_with_mode<R: ark_std::io::Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let window = u32::deserialize_with_mode(&mut reader, compress, validate)?;
+        let max_bits = u32::deserialize_with_mode(&mut reader, compress, validate)?;
+        let table = Vec::deserialize_with_mode(&mut reader, compress, validate)?;
+
+        let t = Self { window, max_bits, table };
+        if validate == Validate::Yes {
+            t.check()?;
+        }
+        Ok(t)
+    }
+}
+
+// The table must have the shape new gives it for window and max_bits, otherwise mul would index
+// past a row or silently drop the top windows of the exponent. The forms are checked by the Vec.
+impl<T: ClassConfig> Valid for FixedBaseTable<T> {
+    fn check(&self) -> Result<(), SerializationError> {
+        let (num_windows, row_len) =
+            Self::dimensions(self.max_bits, self.window).ok_or(SerializationError::InvalidData)?;
+        if self.table.len() != num_windows as usize || self.table.iter().any(|row| row.len() != row_len) {
+            return Err(SerializationError::InvalidData);
+        }
+        Ok(())
+    }
+}
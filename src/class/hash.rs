@@ -0,0 +1,53 @@
+use ark_std::{One, Zero};
+
+use crate::class::{config::ClassConfig, ClassGroup};
+use crate::{shake::Shake256, Integer};
+
+// Default bit size of the primes hash_to_group maps to, see ClassConfig::hash_prime_bits
+pub const HASH_PRIME_BITS: u32 = 256;
+
+// Fixed encoding of D for hashing: a sign byte, 1 if negative, then |D| in big-endian bytes
+pub(super) fn discriminant_bytes<T: ClassConfig>() -> Vec<u8> {
+    let disc = T::discriminant();
+    let abs = disc.abs();
+    let mut bytes = vec![u8::from(disc < T::Int::zero())];
+    bytes.extend((0..abs.significant_bits().div_ceil(8)).rev().map(|i| abs.shr_to_i64_wrapping(8 * i) as u8));
+    bytes
+}
+
+// Length-prefixed input so that (domain, msg) pairs cannot collide
+fn absorb(xof: &mut Shake256, data: &[u8]) {
+    xof.update(&(data.len() as u64).to_le_bytes());
+    xof.update(data);
+}
+
+impl<T: ClassConfig> ClassGroup<T> {
+    // Deterministic map from (domain, msg) to Cl(D), e.g. as a random oracle for Wesolowski proofs.
+    // SHAKE256 over domain, D and msg yields odd candidates p of exactly hash_prime_bits bits, and
+    // the first prime with (D / p) = 1 gives the result ClassGroup::prime_form(p).
+    pub fn hash_to_group(domain: &[u8], msg: &[u8]) -> Self {
+        let bits = T::hash_prime_bits();
+        assert!(bits >= 3, "hash primes need at least 3 bits");
+
+        let mut xof = Shake256::new();
+        absorb(&mut xof, domain);
+        absorb(&mut xof, &discriminant_bytes::<T>());
+        absorb(&mut xof, msg);
+
+        let mut bytes = vec![0u8; bits.div_ceil(8) as usize];
+        let top = T::Int::from(2u8).pow(&(bits - 1));
+        loop {
+            // Big endian, cut to bits - 1 bits, then the top bit set and made odd
+            xof.squeeze(&mut bytes);
+            let n = bytes.iter().fold(T::Int::zero(), |n, &x| n * &T::Int::from(256u32) + T::Int::from(x));
+            let mut p = (n >> (8 * bytes.len() as u32 - bits + 1)) + &top;
+            if !p.is_odd() {
+                p += &T::Int::one();
+            }
+
+            if let Ok(f) = Self::prime_form(p) {
+                return f;
+            }
+        }
+    }
+}
@@ -1,21 +1,39 @@
 use serde::{Deserialize, Serialize};
 use ark_serialize::{
-    CanonicalDeserialize, CanonicalSerialize, Compress, Valid, Validate
+    CanonicalDeserialize, CanonicalSerialize,
+    Compress, SerializationError, Valid, Validate
 };
-use ark_std::{rand::{distributions::Standard, prelude::Distribution, Rng}, One, Zero};
+use ark_std::{rand::Rng, One, Zero};
 use zeroize::Zeroize;
 
 use ark_std::{
+    borrow::Borrow,
     fmt::{Debug, Display},
     hash::Hash,
     iter::*,
     ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
-use crate::AdditiveGroup;
-use crate::class::config::{ClassConfig, TestClassConfig};
+use crate::{msm::wnaf, secret::Secret, AdditiveGroup, Integer};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use crate::class::{
+    config::{ClassConfig, FormConfig},
+    context::{with_thread_context, ClassGroupContext},
+    sample::random_bits,
+};
 
 pub mod config;
+pub mod context;
+pub mod fixed_base;
+pub mod hash;
+pub mod number;
+pub mod order;
+pub mod sample;
+pub mod structure;
+pub mod unreduced;
+pub mod vec;
 
 // Class group compressed
 #[derive(Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
@@ -27,14 +45,127 @@ pub struct ClassGroupCompressed <T: ClassConfig> {
     pub is_neg: bool,
 }
 
+// Size of the random multiplier in pow_secret_blinded
+pub const SECRET_BLINDING_BITS: u32 = 128;
+
+// Exponentiation strategy for NUPOW
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum PowStrategy {
+    // Signed sliding window (wNAF) over squarings
+    #[default]
+    Wnaf,
+    // Double-base {2,3} chain over squarings and NUCUBE cubings. Opt-in, as benches/nupow.rs only has
+    // it ahead of Wnaf for some exponents below about 512 bits, by less than the run-to-run noise, and
+    // 5 to 60% behind for longer ones
+    DoubleBase,
+}
+
+// Why a triple (a, b, c) is not a valid form of the class group
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ClassGroupError {
+    // b^2 - 4ac differs from the discriminant of the config
+    WrongDiscriminant,
+    // gcd(a, b, c) > 1
+    NotPrimitive,
+    // a <= 0, so the form is not positive definite
+    NonPositive,
+    // 4a does not divide b^2 - D in from_ab
+    NotDivisible,
+    // p is not a prime in prime_form
+    NotPrime,
+    // D is not a nonzero square mod p in prime_form, i.e. (D / p) != 1
+    NotSplit,
+    // The exponent of FixedBaseTable::mul has more than max_bits bits, or the one of pow_secret more
+    // than its bits
+    ExponentTooLarge,
+    // The window of FixedBaseTable::new is not in 1..32, or too narrow for the number of windows of
+    // max_bits to fit in a u32
+    InvalidWindow,
+}
+
+impl Display for ClassGroupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            Self::WrongDiscriminant => "discriminant of the form differs from the config",
+            Self::NotPrimitive => "form is not primitive",
+            Self::NonPositive => "leading coefficient is not positive",
+            Self::NotDivisible => "4a does not divide b^2 - D",
+            Self::NotPrime => "p is not prime",
+            Self::NotSplit => "p does not split, Kronecker symbol (D / p) is not 1",
+            Self::ExponentTooLarge => "exponent exceeds the fixed-base table or the public bit length",
+            Self::InvalidWindow => "window of the fixed-base table is out of range for max_bits",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl std::error::Error for ClassGroupError {}
+
+// Rounds of the primality test in prime_form
+const PRIME_FORM_REPS: u32 = 30;
+
 // Class group uncompressed
-#[derive(Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash, CanonicalSerialize, CanonicalDeserialize)]
-pub struct ClassGroup <T: ClassConfig> {
+#[derive(Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash, CanonicalSerialize)]
+pub struct ClassGroup <T: FormConfig> {
     pub a: T::Int,
     pub b: T::Int,
     pub c: T::Int,
 }
 
+impl<T: FormConfig> ClassGroup<T> {
+    // Unchecked constructor - only use when discriminant is guaranteed to be equal
+    pub fn new_unchecked(a: T::Int, b: T::Int, c: T::Int) -> Self {
+        Self { a, b, c }
+    }
+
+    // prime_form_unreduced for the discriminant disc instead of the one of the config
+    pub(crate) fn prime_form_unreduced_of(disc: &T::Int, p: T::Int) -> Result<Self, ClassGroupError> {
+        if p < T::Int::from(2) {
+            return Err(ClassGroupError::NotPrime);
+        }
+        if disc.kronecker(&p) != 1 {
+            return Err(ClassGroupError::NotSplit);
+        }
+        if !p.is_probably_prime(PRIME_FORM_REPS) {
+            return Err(ClassGroupError::NotPrime);
+        }
+
+        // b^2 = D mod p and b = D mod 2, so b^2 = D mod 4. For p = 2, D = 1 mod 8 and b = 1
+        let mut b = match disc.sqrt_mod_prime(&p) {
+            Some(b) if p.is_odd() => b,
+            _ => T::Int::one(),
+        };
+        if b.is_odd() != disc.is_odd() {
+            b = p.clone() - &b;
+        }
+
+        let mut c = b.clone() * &b - disc;
+        c.div_exact(&(T::Int::from(4) * &p));
+        Ok(Self::new_unchecked(p, b, c))
+    }
+
+    // Principal form (1, b, (b^2 - D) / 4) with b = D mod 2, the neutral element of discriminant disc
+    pub(crate) fn principal(disc: &T::Int) -> Self {
+        let b = T::Int::from(disc.is_odd());
+        let c = (b.clone() - disc) >> 2;
+        Self { a: T::Int::one(), b, c }
+    }
+
+    // Whether a reduced form is the principal one, i.e. a = 1, without knowing the discriminant
+    pub(crate) fn is_reduced_principal(&self) -> bool {
+        self.a.is_one()
+    }
+
+    // Principal form (1, b, (b^2 - D) / 4) with b = D mod 2 of the discriminant D of f
+    pub(crate) fn principal_of(f: &Self) -> Self {
+        // (b0^2 - D) / 4 = ac - (b^2 - b0^2) / 4 with b0 = b mod 2
+        let b = T::Int::from(f.b.is_odd());
+        let mut c = f.b.clone() * &f.b - &b;
+        c = f.a.clone() * &f.c - (c >> 2);
+        Self { a: T::Int::one(), b, c }
+    }
+}
+
 impl <T: ClassConfig> ClassGroup<T> {
     // Print the discriminant from the config
     pub fn discriminant(&self) -> T::Int {
@@ -46,11 +177,6 @@ impl <T: ClassConfig> ClassGroup<T> {
         T::default_nucomp_bound()
     }
 
-    // Unchecked constructor - only use when discriminant is guaranteed to be equal
-    pub fn new_unchecked(a: T::Int, b: T::Int, c: T::Int) -> Self {
-        Self { a, b, c }
-    }
-
     // Constructor - checks that discriminant is equal
     pub fn new(a: T::Int, b: T::Int, c: T::Int) -> Self {
         let disc = b.clone() * b.clone() - T::Int::from(4) * a.clone() * c.clone();
@@ -59,147 +185,1103 @@ impl <T: ClassConfig> ClassGroup<T> {
         Self { a, b, c }
     }
 
+    // Checked constructor - the form must be primitive, positive definite and of the right discriminant
+    pub fn try_new(a: T::Int, b: T::Int, c: T::Int) -> Result<Self, ClassGroupError> {
+        let f = Self { a, b, c };
+        f.check_form()?;
+        Ok(f)
+    }
+
+    // The checks of try_new
+    fn check_form(&self) -> Result<(), ClassGroupError> {
+        if self.a <= T::Int::zero() {
+            return Err(ClassGroupError::NonPositive);
+        }
+
+        let disc = self.b.clone() * &self.b - T::Int::from(4) * self.a.clone() * &self.c;
+        if disc != T::discriminant() {
+            return Err(ClassGroupError::WrongDiscriminant);
+        }
+
+        if !self.is_primitive() {
+            return Err(ClassGroupError::NotPrimitive);
+        }
+        Ok(())
+    }
+
+    // Form (a, b, c) with c = (b^2 - D) / 4a, checked as in try_new
+    pub fn from_ab(a: T::Int, b: T::Int) -> Result<Self, ClassGroupError> {
+        if a <= T::Int::zero() {
+            return Err(ClassGroupError::NonPositive);
+        }
+
+        let mut c = b.clone() * &b - T::discriminant();
+        let four_a = T::Int::from(4) * &a;
+        if !c.is_divisible(&four_a) {
+            return Err(ClassGroupError::NotDivisible);
+        }
+        c.div_exact(&four_a);
+
+        Self::try_new(a, b, c)
+    }
+
+    // Reduced form of the prime form (p, b, c) for a prime p with (D / p) = 1, where b is the square
+    // root of D mod 4p in (0, p). The other root gives the inverse.
+    pub fn prime_form(p: T::Int) -> Result<Self, ClassGroupError> {
+        let mut f = Self::prime_form_unreduced(p)?;
+        f.reduce();
+        Ok(f)
+    }
+
+    // The form (p, b, c) of prime_form before reduction
+    pub(crate) fn prime_form_unreduced(p: T::Int) -> Result<Self, ClassGroupError> {
+        Self::prime_form_unreduced_of(&T::discriminant(), p)
+    }
+
+    // -a < b <= a
+    pub fn is_normal(&self) -> bool {
+        -self.a.clone() < self.b && self.b <= self.a
+    }
+
+    // Normal with a <= c, and b >= 0 if a = c
+    pub fn is_reduced(&self) -> bool {
+        self.is_normal() && self.a <= self.c && (self.a != self.c || self.b >= T::Int::zero())
+    }
+
+    // gcd(a, b, c) = 1
+    pub fn is_primitive(&self) -> bool {
+        let mut g = self.a.clone();
+        let mut s = self.b.clone();
+        let mut t = T::Int::zero();
+        g.extended_gcd_mut(&mut s, &mut t);
+        s.clone_from(&self.c);
+        g.extended_gcd_mut(&mut s, &mut t);
+        g.is_one()
+    }
+
+    // The reduced principal form, i.e. the neutral element
+    pub fn is_identity(&self) -> bool {
+        self.is_zero()
+    }
 }
 
-impl ClassGroup<TestClassConfig> {
+impl<T: FormConfig> ClassGroup<T> {
     // Normalize
-    pub fn normalize(&mut self) {
-        let mut q = self.b.clone();
-        let mut r = self.a.clone();
-        
-        q.div_rem_ceil_mut(&mut r);
+    pub fn normalize(&mut self)
+    where
+        T: ClassConfig,
+    {
+        with_thread_context(|ctx| self.normalize_in_place(ctx));
+    }
+
+    // Normalize, with the temporaries taken from ctx
+    pub fn normalize_in_place(&mut self, ctx: &mut ClassGroupContext<T>) {
+        let ClassGroupContext { q, t: r, .. } = ctx;
+
+        q.clone_from(&self.b);
+        r.clone_from(&self.a);
+        q.div_rem_ceil_mut(r);
         if q.is_odd() {
-            r += self.a.clone();
+            *r += &self.a;
         }
-        q = q >> 1;
-        std::mem::swap(&mut r, &mut self.b);
-        r += self.b.clone();
-        r = r >> 1;
-        self.c -= q * r;
+        *q = std::mem::take(q) >> 1;
+        std::mem::swap(r, &mut self.b);
+        *r += &self.b;
+        *r = std::mem::take(r) >> 1;
+        self.c.sub_mul(q, r);
     }
 
     // Reduction
-    fn rho(&mut self) {
+    fn rho(&mut self, ctx: &mut ClassGroupContext<T>) {
         std::mem::swap(&mut self.a, &mut self.c);
         self.b.neg_in_place();
-        self.normalize();
+        self.normalize_in_place(ctx);
+    }
+
+    pub fn reduce(&mut self)
+    where
+        T: ClassConfig,
+    {
+        with_thread_context(|ctx| self.reduce_in_place(ctx));
     }
 
-    pub fn reduce(&mut self) {
+    // Reduce, with the temporaries taken from ctx
+    pub fn reduce_in_place(&mut self, ctx: &mut ClassGroupContext<T>) {
+        self.normalize_in_place(ctx);
         while self.a > self.c {
-            self.rho();
+            // Word-level pass first, one exact step whenever the approximation cannot decide
+            if !self.lehmer_pass(ctx) {
+                self.rho(ctx);
+            }
         }
 
-        if self.a == self.c {
+        if self.a == self.c && self.b < T::Int::zero() {
             self.b.neg_in_place();
         }
     }
 
+    // Runs rho steps on the leading words of a, b, c and applies the accumulated transformation
+    // to the full coefficients in one pass, then normalizes. Every step is certified to have a > c,
+    // so a strictly decreases. Returns false if too few steps could be taken to pay for the pass.
+    fn lehmer_pass(&mut self, ctx: &mut ClassGroupContext<T>) -> bool {
+        let bits = self.a.significant_bits().max(self.c.significant_bits());
+        if bits <= LEHMER_MIN_BITS {
+            return false;
+        }
+
+        // Truncated approximations in units of 2^shift, a and c fit in 61 bits and |b| <= a
+        let shift = bits - 61;
+        let approx = |x: &T::Int| x.shr_to_i64_wrapping(shift) as i128;
+        let Some(([p, q, r, s], normalized)) = lehmer_matrix(approx(&self.a), approx(&self.b), approx(&self.c))
+        else {
+            return false;
+        };
+
+        // (a, b, c) <- f(p x + q y, r x + s y), all products of two entries fit in i64
+        let ClassGroupContext { q1: a, q2: b, q3: c, t: k, .. } = ctx;
+        let mut combine = |r: &mut T::Int, ka: i64, kb: i64, kc: i64| {
+            k.assign_i64(ka);
+            r.assign_mul(k, &self.a);
+            k.assign_i64(kb);
+            r.add_mul(k, &self.b);
+            k.assign_i64(kc);
+            r.add_mul(k, &self.c);
+        };
+        combine(a, p * p, p * r, r * r);
+        combine(b, 2 * p * q, p * s + q * r, 2 * r * s);
+        combine(c, q * q, q * s, s * s);
+        std::mem::swap(&mut self.a, a);
+        std::mem::swap(&mut self.b, b);
+        std::mem::swap(&mut self.c, c);
+        if !normalized {
+            self.normalize_in_place(ctx);
+        }
+        true
+    }
+
     // NUDUPL
-    pub fn nudupl(r: &mut Self, f: &Self) {
+    pub fn nudupl(r: &mut Self, f: &Self)
+    where
+        T: ClassConfig,
+    {
+        with_thread_context(|ctx| Self::nudupl_into(ctx, r, f));
+    }
+
+    // NUDUPL without the final reduction, the result is only partially reduced
+    pub fn nudupl_unreduced(r: &mut Self, f: &Self)
+    where
+        T: ClassConfig,
+    {
+        with_thread_context(|ctx| Self::nudupl_unreduced_into(ctx, r, f));
+    }
+
+    // NUDUPL, with the temporaries taken from ctx
+    pub fn nudupl_into(ctx: &mut ClassGroupContext<T>, r: &mut Self, f: &Self) {
+        Self::nudupl_unreduced_into(ctx, r, f);
+        r.reduce_in_place(ctx);
+    }
+
+    // NUDUPL without the final reduction, with the temporaries taken from ctx
+    pub fn nudupl_unreduced_into(ctx: &mut ClassGroupContext<T>, r: &mut Self, f: &Self) {
         // f = (a,b,c)
         // r = result
+        let ClassGroupContext {
+            bound, half_gcd_threshold_bits, d, u, v, s, bx, by, x, y, dx, dy, ua, wc, q1, q, t, ..
+        } = ctx;
 
         // d = gcd(a,b) = ua + vb
-        let mut d = f.a.clone();
-        let mut u = f.b.clone();
-        let mut v = <TestClassConfig as ClassConfig>::Int::zero();
+        d.clone_from(&f.a);
+        u.clone_from(&f.b);
+        d.extended_gcd_mut(u, v);
 
-        d.extended_gcd_mut(&mut u, &mut v);
-
-        r.a = f.a.clone();
-        r.b = f.b.clone();
+        r.a.clone_from(&f.a);
+        r.b.clone_from(&f.b);
 
         if !d.is_one() {
-            r.a.div_exact(&mut d);
-            r.b.div_exact(&mut d);
+            r.a.div_exact(d);
+            r.b.div_exact(d);
         }
-        
+
         // Dx = -uc
-        let mut dx = f.c.clone();
-        dx.mul_assign(&mut u);
+        dx.assign_mul(u, &f.c);
         dx.neg_in_place();
 
-        // r.c = vc
-        r.c = f.c.clone();
-        r.c.mul_assign(&mut v);
+        // (q, r.c) = divmod(vc, r.a)
+        q.assign_mul(v, &f.c);
+        t.clone_from(&r.a);
+        q.div_rem_floor_mut(t);
+        std::mem::swap(&mut r.c, t);
 
-        let mut q = r.c.clone();
-        let mut rr = r.a.clone();
-        q.div_rem_floor_mut(&mut rr);
-        r.c = rr;
+        dx.sub_mul(q, &r.b);
 
-        dx -= q * r.b.clone();
+        // Partial euclid on (By, Bx) = (r.a, r.c)
+        by.clone_from(&r.a);
+        bx.clone_from(&r.c);
+        x.assign_i64(1);
+        y.assign_i64(0);
+        let z = partial_euclid::<T>(by, bx, x, y, bound, *half_gcd_threshold_bits, q, t);
 
-        todo!("Partial euclid and rest");
+        s.clone_from(bx);
+        *s += &*by;
+        ua.assign_mul(by, by);
+        wc.assign_mul(bx, bx);
+
+        if z == 0 {
+            // a = By^2, b = b - (Bx + By)^2 + a + c, c = Bx^2 - d * dx
+            r.b.clone_from(&f.b);
+            r.b.sub_mul(s, s);
+            r.b += &*ua;
+            r.b += &*wc;
+            r.c.clone_from(wc);
+            r.c.sub_mul(d, dx);
+            std::mem::swap(&mut r.a, ua);
+        } else {
+            // dx = (Bx * Dy - c * x) / By
+            dx.assign_mul(bx, &r.b);
+            dx.sub_mul(&f.c, x);
+            dx.div_exact(&r.a);
+
+            // dy = (y * dx + Dy) / x, b = d * (2 * y * dx + Dy)
+            q1.assign_mul(y, dx);
+            dy.clone_from(q1);
+            *dy += &r.b;
+            t.clone_from(dy);
+            *t += &*q1;
+            r.b.assign_mul(d, t);
+            dy.div_exact(x);
+
+            // a = By^2 - d * y * dy, b = b - (Bx + By)^2 + By^2 + Bx^2, c = Bx^2 - d * x * dx
+            r.b.sub_mul(s, s);
+            r.b += &*ua;
+            r.b += &*wc;
+            t.assign_mul(y, dy);
+            r.a.clone_from(ua);
+            r.a.sub_mul(d, t);
+            t.assign_mul(x, dx);
+            r.c.clone_from(wc);
+            r.c.sub_mul(d, t);
+        }
+    }
+
+    // NUCUBE
+    pub fn nucube(r: &mut Self, f: &Self)
+    where
+        T: ClassConfig,
+    {
+        with_thread_context(|ctx| Self::nucube_into(ctx, r, f));
+    }
+
+    // NUCUBE without the final reduction, the result is only partially reduced
+    pub fn nucube_unreduced(r: &mut Self, f: &Self)
+    where
+        T: ClassConfig,
+    {
+        with_thread_context(|ctx| Self::nucube_unreduced_into(ctx, r, f));
+    }
+
+    // NUCUBE, with the temporaries taken from ctx
+    pub fn nucube_into(ctx: &mut ClassGroupContext<T>, r: &mut Self, f: &Self) {
+        Self::nucube_unreduced_into(ctx, r, f);
+        r.reduce_in_place(ctx);
+    }
+
+    // NUCUBE of Imbert, Jacobson and Schmidt, with the temporaries taken from ctx. For gcd(a, b) = 1
+    // the cube is (a^3, b + 2aK, ..) with aK^2 + bK + c = 0 mod a^2, which is the composite of the
+    // square (a^2, b + 2aK0, ..), K0 = K mod a, with f in NUCOMP. It is partially reduced with a single
+    // partial Euclid on (a^2, -K mod a^2) down to sqrt(a) |D/4|^(1/4), instead of one for the square and
+    // one for the composition, which makes it 5 to 15% faster than NUDUPL followed by NUCOMP in
+    // benches/nucube.rs. Otherwise NUDUPL followed by NUCOMP, which allocates the square.
+    pub fn nucube_unreduced_into(ctx: &mut ClassGroupContext<T>, r: &mut Self, f: &Self) {
+        let ClassGroupContext { s, m, f: e, g, h, l, u, v, a1g, a2g, sg, bx, t, .. } = ctx;
+
+        // e = gcd(a, b) = v * a + u * b, so u = b^-1 mod a
+        e.clone_from(&f.a);
+        v.clone_from(&f.b);
+        e.extended_gcd_mut(v, u);
+        if !e.is_one() {
+            let mut sq = Self::default();
+            Self::nudupl_unreduced_into(ctx, &mut sq, f);
+            Self::nucomp_unreduced_into(ctx, r, &sq, f);
+            return;
+        }
+
+        // h = K0 = -c * u mod a, l = (a * K0^2 + b * K0 + c) / a, the c of the square
+        h.assign_mul(&f.c, u);
+        h.neg_in_place();
+        h.rem_floor_mut(&f.a);
+        l.assign_mul(&f.a, h);
+        *l += &f.b;
+        t.assign_mul(l, h);
+        *t += &f.c;
+        t.div_exact(&f.a);
+        std::mem::swap(l, t);
+
+        // K = K0 + a * (-l * u mod a), Bx = -K mod a^2
+        t.assign_mul(l, u);
+        t.neg_in_place();
+        t.rem_floor_mut(&f.a);
+        bx.clone_from(h);
+        bx.add_mul(&f.a, t);
+        bx.neg_in_place();
+        a1g.assign_mul(&f.a, &f.a);
+        bx.rem_floor_mut(a1g);
+
+        // As NUCOMP of the square with f: g = 1, m = b - s = -a * K0, By = a^2, Cy = a, Dy = s = b + a * K0
+        g.assign_i64(1);
+        m.assign_mul(&f.a, h);
+        m.neg_in_place();
+        s.clone_from(&f.b);
+        *s -= &*m;
+        sg.clone_from(s);
+        a2g.clone_from(&f.a);
+
+        let c1 = std::mem::take(&mut ctx.l);
+        Self::nucomp_finish(ctx, r, &c1, f, true);
+        ctx.l = c1;
     }
 
     // NUCOMP
-    pub fn nucomp(_r: &mut Self, _f1: &Self, _f2: &Self) {
-        todo!("NUCOMP");
+    pub fn nucomp(r: &mut Self, f1: &Self, f2: &Self)
+    where
+        T: ClassConfig,
+    {
+        with_thread_context(|ctx| Self::nucomp_into(ctx, r, f1, f2));
+    }
+
+    // NUCOMP without the final reduction, the result is only partially reduced
+    pub fn nucomp_unreduced(r: &mut Self, f1: &Self, f2: &Self)
+    where
+        T: ClassConfig,
+    {
+        with_thread_context(|ctx| Self::nucomp_unreduced_into(ctx, r, f1, f2));
+    }
+
+    // NUCOMP, with the temporaries taken from ctx
+    pub fn nucomp_into(ctx: &mut ClassGroupContext<T>, r: &mut Self, f1: &Self, f2: &Self) {
+        Self::nucomp_unreduced_into(ctx, r, f1, f2);
+        r.reduce_in_place(ctx);
+    }
+
+    // NUCOMP without the final reduction, with the temporaries taken from ctx
+    pub fn nucomp_unreduced_into(ctx: &mut ClassGroupContext<T>, r: &mut Self, f1: &Self, f2: &Self) {
+        // f1 = (a1,b1,c1), f2 = (a2,b2,c2) with a1 >= a2
+        // r = result
+        if f1.a < f2.a {
+            return Self::nucomp_unreduced_into(ctx, r, f2, f1);
+        }
+
+        let ClassGroupContext { s, m, f, g, h, l, u, v, w, a1g, a2g, sg, bx, t, .. } = ctx;
+
+        // s = (b1 + b2) / 2, m = b2 - s
+        s.clone_from(&f1.b);
+        *s += &f2.b;
+        *s = std::mem::take(s) >> 1;
+        m.clone_from(&f2.b);
+        *m -= &*s;
+
+        // f = gcd(a1,a2) = v * a1 + u * a2
+        f.clone_from(&f1.a);
+        v.clone_from(&f2.a);
+        f.extended_gcd_mut(v, u);
+
+        // g = gcd(f,s) = ? * f + w * s, only needed when a1 and a2 are not coprime to s
+        let s_divisible = s.is_divisible(f);
+        g.clone_from(f);
+        if !s_divisible {
+            t.clone_from(s);
+            g.extended_gcd_mut(t, w);
+        }
+
+        // By = a1 / g, Cy = a2 / g, Dy = s / g
+        a1g.clone_from(&f1.a);
+        a2g.clone_from(&f2.a);
+        sg.clone_from(s);
+        if !g.is_one() {
+            a1g.div_exact(g);
+            a2g.div_exact(g);
+            sg.div_exact(g);
+        }
+
+        if s_divisible {
+            // Bx = m * u
+            bx.assign_mul(m, u);
+        } else {
+            // h = f / g, l = w * (u * (c1 mod h) + v * (c2 mod h)) mod h
+            h.clone_from(f);
+            h.div_exact(g);
+            t.clone_from(&f1.c);
+            t.rem_floor_mut(h);
+            l.assign_mul(u, t);
+            t.clone_from(&f2.c);
+            t.rem_floor_mut(h);
+            l.add_mul(v, t);
+            t.assign_mul(w, l);
+            t.rem_floor_mut(h);
+            std::mem::swap(l, t);
+
+            // Bx = u * (m / h) + l * (By / h)
+            t.clone_from(m);
+            t.div_exact(h);
+            bx.assign_mul(u, t);
+            t.clone_from(a1g);
+            t.div_exact(h);
+            bx.add_mul(l, t);
+        }
+        bx.rem_floor_mut(a1g);
+
+        Self::nucomp_finish(ctx, r, &f1.c, f2, false);
+    }
+
+    // Second half of NUCOMP, shared with NUCUBE: partial Euclid on (By, Bx) and the composite from the
+    // remainders. Expects g, m, By = a1g, Cy = a2g, Dy = sg and Bx as NUCOMP sets them up in ctx, c1 the
+    // c of the first form and f2 the second form. The Euclid stops at the NUCOMP bound |D/4|^(1/4), or
+    // for a cube of f2 at sqrt(a2) |D/4|^(1/4) as By = a2^2 is then larger than for a product.
+    fn nucomp_finish(ctx: &mut ClassGroupContext<T>, r: &mut Self, c1: &T::Int, f2: &Self, cube: bool) {
+        let ClassGroupContext {
+            bound, half_gcd_threshold_bits, m, g, w, x, y, a1g, a2g, sg, bx, by, cx, cy, dx, dy, q1, q2, q3, q4, q, t, ..
+        } = ctx;
+        let mut bound = &*bound;
+        if cube {
+            // w = bound * floor(a2 / 2^(bits / 2)), about bound * sqrt(a2)
+            t.clone_from(&f2.a);
+            *t = std::mem::take(t) >> (f2.a.significant_bits() / 2);
+            w.assign_mul(t, bound);
+            bound = w;
+        }
+
+        // Partial euclid on (By, Bx)
+        by.clone_from(a1g);
+        x.assign_i64(1);
+        y.assign_i64(0);
+        let z = partial_euclid::<T>(by, bx, x, y, bound, *half_gcd_threshold_bits, q, t);
+
+        if z == 0 {
+            // q1 = Cy * Bx, cx = (q1 - m) / By, dx = (Bx * Dy - c2) / By
+            q1.assign_mul(a2g, bx);
+            cx.clone_from(q1);
+            *cx -= &*m;
+            cx.div_exact(a1g);
+            dx.assign_mul(bx, sg);
+            *dx -= &f2.c;
+            dx.div_exact(a1g);
+
+            // a = By * Cy, b = b2 - 2 * q1, c = Bx * cx - g * dx
+            r.a.assign_mul(by, a2g);
+            r.b.clone_from(&f2.b);
+            r.b -= &*q1;
+            r.b -= &*q1;
+            r.c.assign_mul(bx, cx);
+            r.c.sub_mul(g, dx);
+        } else {
+            // cx = (Cy * bx - m * x) / By, q1 = by * cx, q2 = q1 + m
+            cx.assign_mul(a2g, bx);
+            cx.sub_mul(m, x);
+            cx.div_exact(a1g);
+            q1.assign_mul(by, cx);
+            q2.clone_from(q1);
+            *q2 += &*m;
+
+            // dx = (Dy * bx - c2 * x) / By, q3 = y * dx, q4 = q3 + Dy, dy = q4 / x
+            dx.assign_mul(sg, bx);
+            dx.sub_mul(&f2.c, x);
+            dx.div_exact(a1g);
+            q3.assign_mul(y, dx);
+            q4.clone_from(q3);
+            *q4 += &*sg;
+            dy.clone_from(q4);
+            dy.div_exact(x);
+
+            // cy = q2 / bx, or (cx * dy - c1) / dx when bx vanished
+            if !bx.is_zero() {
+                cy.clone_from(q2);
+                cy.div_exact(bx);
+            } else {
+                cy.assign_mul(cx, dy);
+                *cy -= c1;
+                cy.div_exact(dx);
+            }
+
+            // a = by * cy - g * y * dy, b = g * (q3 + q4) - q1 - q2, c = bx * cx - g * x * dx
+            r.a.assign_mul(by, cy);
+            t.assign_mul(y, dy);
+            r.a.sub_mul(g, t);
+            *q3 += &*q4;
+            r.b.assign_mul(g, q3);
+            r.b -= &*q1;
+            r.b -= &*q2;
+            r.c.assign_mul(bx, cx);
+            t.assign_mul(x, dx);
+            r.c.sub_mul(g, t);
+        }
+    }
+
+    // Classical composition through Dirichlet's united forms followed by a full reduction.
+    // Much slower than NUCOMP, meant as an independent reference to check NUCOMP, NUDUPL and NUPOW against.
+    pub fn compose_classical(r: &mut Self, f1: &Self, f2: &Self)
+    where
+        T: ClassConfig,
+    {
+        let disc = T::discriminant();
+
+        // s = (b1 + b2) / 2
+        let s = (f1.b.clone() + &f2.b) >> 1;
+
+        // e = gcd(a1, a2, s) = u * a1 + v * a2 + w * s
+        let mut e1 = f1.a.clone();
+        let mut x1 = f2.a.clone();
+        let mut y1 = T::Int::zero();
+        e1.extended_gcd_mut(&mut x1, &mut y1);
+
+        let mut e = e1;
+        let mut x2 = s;
+        let mut w = T::Int::zero();
+        e.extended_gcd_mut(&mut x2, &mut w);
+
+        let u = x1 * &x2;
+        let v = y1 * &x2;
+
+        // a = a1 * a2 / e^2
+        let mut a = f1.a.clone() * &f2.a;
+        a.div_exact(&(e.clone() * &e));
+
+        // b = (u * a1 * b2 + v * a2 * b1 + w * (b1 * b2 + D) / 2) / e mod 2a
+        let t = (f1.b.clone() * &f2.b + &disc) >> 1;
+        let mut b = u * &f1.a * &f2.b + v * &f2.a * &f1.b + w * t;
+        b.div_exact(&e);
+        b = b.rem_floor(&(T::Int::from(2) * &a));
+
+        // c = (b^2 - D) / 4a
+        let mut c = b.clone() * &b - disc;
+        c.div_exact(&(T::Int::from(4) * &a));
+
+        *r = Self::new_unchecked(a, b, c);
+        r.reduce();
     }
 
     // NUPOW
-    pub fn nupow(_r: &mut Self, _f: &Self, _n: &<TestClassConfig as ClassConfig>::Int) {
-        todo!("NUPOW");
+    pub fn nupow(r: &mut Self, f: &Self, n: &T::Int)
+    where
+        T: ClassConfig,
+    {
+        with_thread_context(|ctx| Self::nupow_into(ctx, r, f, n));
+    }
+
+    // NUPOW, with the temporaries taken from ctx
+    pub fn nupow_into(ctx: &mut ClassGroupContext<T>, r: &mut Self, f: &Self, n: &T::Int) {
+        Self::nupow_with_into(ctx, r, f, n, PowStrategy::default());
+    }
+
+    // NUPOW with an explicit exponentiation strategy
+    pub fn nupow_with(r: &mut Self, f: &Self, n: &T::Int, strategy: PowStrategy)
+    where
+        T: ClassConfig,
+    {
+        with_thread_context(|ctx| Self::nupow_with_into(ctx, r, f, n, strategy));
+    }
+
+    // nupow_with, with the temporaries taken from ctx
+    pub fn nupow_with_into(ctx: &mut ClassGroupContext<T>, r: &mut Self, f: &Self, n: &T::Int, strategy: PowStrategy) {
+        // f = base, n = exponent
+        // r = result
+        if n.is_zero() {
+            *r = Self::principal_of(f);
+            return;
+        }
+
+        match strategy {
+            PowStrategy::Wnaf => Self::nupow_wnaf(ctx, r, f, &n.abs()),
+            PowStrategy::DoubleBase => Self::nupow_double_base(ctx, r, f, &n.abs()),
+        }
+
+        if *n < T::Int::zero() {
+            r.b.neg_in_place();
+            r.reduce_in_place(ctx);
+        }
+    }
+
+    // f^n for n > 0 with a wNAF recoding
+    fn nupow_wnaf(ctx: &mut ClassGroupContext<T>, r: &mut Self, f: &Self, n: &T::Int) {
+        // Signed digits of n, inversion is free so negative digits cost the same as positive ones
+        let w = nupow_window(n.significant_bits());
+        let digits = wnaf(n, w);
+
+        // Odd powers f, f^3, ..., f^(2^(w-1) - 1) and their inverses
+        let mut table = vec![f.clone()];
+        if w > 2 {
+            let mut f2 = Self::default();
+            Self::nudupl_into(ctx, &mut f2, f);
+            for i in 1..(1 << (w - 2)) {
+                let mut t = Self::default();
+                Self::nucomp_into(ctx, &mut t, &table[i - 1], &f2);
+                table.push(t);
+            }
+        }
+        let table_inv: Vec<Self> = table.iter().map(|g| -g.clone()).collect();
+
+        // Left to right, starting from the leading (positive) digit
+        let mut iter = digits.iter().rev();
+        let lead = *iter.next().unwrap();
+        *r = table[(lead as usize - 1) / 2].clone();
+
+        let mut t = Self::default();
+        for &d in iter {
+            Self::nudupl_into(ctx, &mut t, r);
+            std::mem::swap(r, &mut t);
+
+            if d > 0 {
+                Self::nucomp_into(ctx, &mut t, r, &table[(d as usize - 1) / 2]);
+                std::mem::swap(r, &mut t);
+            } else if d < 0 {
+                Self::nucomp_into(ctx, &mut t, r, &table_inv[(-d) as usize / 2]);
+                std::mem::swap(r, &mut t);
+            }
+        }
+    }
+
+    // f^n for n > 0 with a double-base {2,3} chain
+    fn nupow_double_base(ctx: &mut ClassGroupContext<T>, r: &mut Self, f: &Self, n: &T::Int) {
+        let chain = double_base_chain(n);
+        let f_inv = -f.clone();
+
+        // Innermost term is f itself, then raise by 2^e2 3^e3 and fold in f^(+-1) going outwards
+        *r = f.clone();
+        let mut t = Self::default();
+        for (i, &(e2, e3, sign)) in chain.iter().enumerate().rev() {
+            if i + 1 < chain.len() {
+                let g = if sign > 0 { f } else { &f_inv };
+                Self::nucomp_into(ctx, &mut t, r, g);
+                std::mem::swap(r, &mut t);
+            }
+            for _ in 0..e3 {
+                Self::nucube_into(ctx, &mut t, r);
+                std::mem::swap(r, &mut t);
+            }
+            for _ in 0..e2 {
+                Self::nudupl_into(ctx, &mut t, r);
+                std::mem::swap(r, &mut t);
+            }
+        }
+    }
+
+    // Exponentiation for secret exponents, with a sequence of squarings and compositions that
+    // depends only on the public bit length `bits` and not on the bits of n.
+    // This is a Montgomery ladder: every bit costs exactly one NUCOMP and one NUDUPL.
+    // NUCOMP, NUDUPL and the reduction are still variable time on their own, and the ladder swap is
+    // a plain branch, so this only closes the leak through the operation schedule.
+    // Fails with ExponentTooLarge if |n| has more than bits bits.
+    pub fn pow_secret(r: &mut Self, f: &Self, n: &T::Int, bits: u32) -> Result<(), ClassGroupError>
+    where
+        T: ClassConfig,
+    {
+        // The sign only decides which base the ladder starts from
+        let base = if *n < T::Int::zero() { -f.clone() } else { f.clone() };
+        let e = Secret::new(n.abs());
+        Self::ladder(r, &base, &e, bits)
+    }
+
+    // pow_secret on the blinded exponent |n| + k * order_multiple, for a fresh random k of
+    // SECRET_BLINDING_BITS bits, so repeated calls never run the ladder on the same exponent.
+    // order_multiple must be a multiple of the order of f, e.g. the class number, otherwise the result changes.
+    // Fails with ExponentTooLarge if |n| has more than bits bits.
+    pub fn pow_secret_blinded<R: Rng + ?Sized>(
+        r: &mut Self,
+        f: &Self,
+        n: &T::Int,
+        bits: u32,
+        order_multiple: &T::Int,
+        rng: &mut R,
+    ) -> Result<(), ClassGroupError>
+    where
+        T: ClassConfig,
+    {
+        if n.significant_bits() > bits {
+            return Err(ClassGroupError::ExponentTooLarge);
+        }
+
+        // k and e are zeroized when dropped, e is built in place so no temporary holds |n|
+        let k = Secret::new(random_bits::<T::Int, R>(SECRET_BLINDING_BITS, rng));
+        let mut e = Secret::new(n.abs());
+        e.expose_mut().add_mul(&k, order_multiple);
+        let bits = bits.max(order_multiple.significant_bits()) + SECRET_BLINDING_BITS + 1;
+
+        let base = if *n < T::Int::zero() { -f.clone() } else { f.clone() };
+        Self::ladder(r, &base, &e, bits)
+    }
+
+    // Montgomery ladder over the `bits` bits of e >= 0, keeping r1 = r0 * f, or ExponentTooLarge if e
+    // has more bits
+    fn ladder(r: &mut Self, f: &Self, e: &T::Int, bits: u32) -> Result<(), ClassGroupError>
+    where
+        T: ClassConfig,
+    {
+        if e.significant_bits() > bits {
+            return Err(ClassGroupError::ExponentTooLarge);
+        }
+
+        let ctx = &mut ClassGroupContext::new();
+        let mut r0 = Self::zero();
+        let mut r1 = f.clone();
+        let mut t = Self::default();
+        for i in (0..bits).rev() {
+            // bit = 0: (r0, r1) = (r0^2, r0 * r1), bit = 1: (r0, r1) = (r0 * r1, r1^2)
+            let bit = e.get_bit(i);
+            if bit {
+                std::mem::swap(&mut r0, &mut r1);
+            }
+
+            Self::nucomp_into(ctx, &mut t, &r0, &r1);
+            std::mem::swap(&mut r1, &mut t);
+            Self::nudupl_into(ctx, &mut t, &r0);
+            std::mem::swap(&mut r0, &mut t);
+
+            if bit {
+                std::mem::swap(&mut r0, &mut r1);
+            }
+        }
+
+        *r = r0;
+
+        // The other half of the ladder and the temporaries depend on the exponent
+        r1.zeroize();
+        t.zeroize();
+        ctx.zeroize();
+        Ok(())
     }
 }
 
-impl AdditiveGroup for ClassGroup<TestClassConfig> {
-    type Scalar = <TestClassConfig as ClassConfig>::Int;
+// Double-base {2,3} chain of n > 0, outermost term first.
+// Each entry (e2, e3, s) stands for n_i = 2^e2 * 3^e3 * (n_{i+1} + s) with s = +-1, and the last
+// entry for 2^e2 * 3^e3 (its sign is unused). Each step takes whichever of m - 1, m + 1 has
+// the larger {2,3}-smooth part, so exponents only decrease along the chain.
+fn double_base_chain<I: Integer>(n: &I) -> Vec<(u32, u32, i8)> {
+    let two = I::from(2);
+    let three = I::from(3);
+
+    // Strip the {2,3}-smooth part of m, returning (e2, e3)
+    let strip = |m: &mut I| {
+        let mut e2 = 0;
+        let mut e3 = 0;
+        while m.is_divisible(&two) {
+            m.div_exact(&two);
+            e2 += 1;
+        }
+        while m.is_divisible(&three) {
+            m.div_exact(&three);
+            e3 += 1;
+        }
+        (e2, e3)
+    };
+
+    let mut chain = Vec::new();
+    let mut m = n.clone();
+    loop {
+        let (e2, e3) = strip(&mut m);
+        if m.is_one() {
+            chain.push((e2, e3, 1));
+            return chain;
+        }
+
+        // log2 of the smooth part, scaled by 1000
+        let mut lo = m.clone() - I::one();
+        let mut hi = m.clone() + I::one();
+        let (lo2, lo3) = strip(&mut lo);
+        let (hi2, hi3) = strip(&mut hi);
+        if 1000 * lo2 + 1585 * lo3 >= 1000 * hi2 + 1585 * hi3 {
+            chain.push((e2, e3, 1));
+            m -= I::one();
+        } else {
+            chain.push((e2, e3, -1));
+            m += I::one();
+        }
+    }
+}
+
+// Window width for NUPOW, minimizing 2^(w-2) precomputed compositions plus bits / (w + 1) in the main loop
+fn nupow_window(bits: u32) -> usize {
+    let cost = |w: usize| (1usize << (w - 2)) + bits as usize / (w + 1);
+    (2..=10).min_by_key(|&w| cost(w)).unwrap()
+}
+
+// Forms with a and c below this size are reduced with exact steps only
+const LEHMER_MIN_BITS: u32 = 64;
+
+// Fewer certified steps than this are left to exact steps, which are cheaper
+const LEHMER_MIN_STEPS: usize = 4;
+
+// Bound on the entries of the Lehmer transformation matrix
+const LEHMER_MAX_ENTRY: i128 = 1 << 30;
+
+// Transformation [p, q, r, s] made of rho steps on the approximation (a, b, c) of a form,
+// stopping as soon as a > c can no longer be certified for the exact form.
+// The truncation error of each approximate coefficient is below 1 in absolute value before the transformation,
+// so afterwards it is below (|p| + |r|)^2 for a, 2 (|p| + |r|) (|q| + |s|) for b and (|q| + |s|)^2 for c.
+// Also returns whether -a < b <= a is certified for the transformed exact form.
+fn lehmer_matrix(mut a: i128, mut b: i128, mut c: i128) -> Option<([i64; 4], bool)> {
+    let (mut p, mut q, mut r, mut s) = (1i128, 0i128, 0i128, 1i128);
+    let mut steps = 0;
+
+    loop {
+        let err_a = (p.abs() + r.abs()).pow(2);
+        let err_c = (q.abs() + s.abs()).pow(2);
+        if c <= err_c || a - c <= err_a + err_c {
+            break;
+        }
+
+        // rho: (a, b, c) <- (c, -b, a), then b <- b - 2ka in (-a, a]
+        // c < a < 2^62 and |b| <= a keep c - b and 2c within i64, whose division is much cheaper
+        let b1 = -b;
+        let k = -((c - b1) as i64).div_euclid(2 * c as i64) as i128;
+        let (q1, s1) = (-p - k * q, -r - k * s);
+        if k.abs() >= LEHMER_MAX_ENTRY || q1.abs() >= LEHMER_MAX_ENTRY || s1.abs() >= LEHMER_MAX_ENTRY {
+            break;
+        }
+
+        let c1 = c * k * k - b1 * k + a;
+        (a, b, c) = (c, b1 - 2 * k * c, c1);
+        (p, q, r, s) = (q, q1, s, s1);
+        steps += 1;
+    }
+
+    if steps < LEHMER_MIN_STEPS {
+        return None;
+    }
+
+    let err_a = (p.abs() + r.abs()).pow(2);
+    let err_b = 2 * (p.abs() + r.abs()) * (q.abs() + s.abs());
+    let normalized = b.abs() + err_b < a - err_a;
+    Some(([p as i64, q as i64, r as i64, s as i64], normalized))
+}
+
+// Partial extended euclid on (by, bx), run until by drops below the bound or bx vanishes, through half
+// gcds while by has more than half_gcd_threshold_bits bits.
+// x and y are the cofactors of bx and by with respect to the initial bx, i.e. bx = x * Bx (mod By).
+// q and t are scratch space. Returns the number of steps, with the signs of by and y already fixed
+// up when it is odd.
+#[allow(clippy::too_many_arguments)]
+fn partial_euclid<T: FormConfig>(
+    by: &mut T::Int,
+    bx: &mut T::Int,
+    x: &mut T::Int,
+    y: &mut T::Int,
+    bound: &T::Int,
+    half_gcd_threshold_bits: u32,
+    q: &mut T::Int,
+    t: &mut T::Int,
+) -> usize {
+    let mut z = 0;
+
+    if by.significant_bits() > half_gcd_threshold_bits {
+        // Same steps through half gcds, from (x, y) = (1, 0)
+        let p = by.partial_xgcd(bx, bound);
+        (*by, *bx, *y, *x) = (p.r0, p.r1, p.t0, p.t1);
+        z = p.steps;
+    }
+
+    // by and bx stay non-negative until the final sign fix
+    while *by > *bound && !bx.is_zero() {
+        // (q, t) = divmod(by, bx)
+        q.clone_from(by);
+        t.clone_from(bx);
+        q.div_rem_floor_mut(t);
+
+        // (by, bx) = (bx, t)
+        std::mem::swap(by, bx);
+        std::mem::swap(bx, t);
+
+        // (y, x) = (x, y - q * x)
+        y.sub_mul(q, x);
+        std::mem::swap(x, y);
+
+        z += 1;
+    }
+
+    if z % 2 == 1 {
+        by.neg_in_place();
+        y.neg_in_place();
+    }
+
+    z
+}
+
+// The batch operations keep one context per thread instead of allocating temporaries per element
+impl<T: ClassConfig> AdditiveGroup for ClassGroup<T> {
+    type Scalar = T::Int;
+
+    fn double_in_place(&mut self) -> &mut Self {
+        let mut r = Self::default();
+        Self::nudupl(&mut r, self);
+        *self = r;
+        self
+    }
+
+    fn neg_in_place(&mut self) -> &mut Self {
+        *self = -std::mem::take(self);
+        self
+    }
+
+    fn batch_add(a: &[Self], b: &[Self]) -> Vec<Self> {
+        assert_eq!(a.len(), b.len(), "batches must have the same length");
+        let add = |ctx: &mut ClassGroupContext<T>, (x, y): (&Self, &Self)| {
+            let mut r = Self::default();
+            Self::nucomp_into(ctx, &mut r, x, y);
+            r
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            a.par_iter().zip(b).map_init(ClassGroupContext::new, add).collect()
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            let mut ctx = ClassGroupContext::new();
+            a.iter().zip(b).map(|xy| add(&mut ctx, xy)).collect()
+        }
+    }
+
+    fn batch_double(a: &[Self]) -> Vec<Self> {
+        let double = |ctx: &mut ClassGroupContext<T>, x: &Self| {
+            let mut r = Self::default();
+            Self::nudupl_into(ctx, &mut r, x);
+            r
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            a.par_iter().map_init(ClassGroupContext::new, double).collect()
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            let mut ctx = ClassGroupContext::new();
+            a.iter().map(|x| double(&mut ctx, x)).collect()
+        }
+    }
+
+    fn batch_scalar_mul(bases: &[Self], scalars: &[T::Int]) -> Vec<Self> {
+        assert_eq!(bases.len(), scalars.len(), "bases and scalars must have the same length");
+        let mul = |ctx: &mut ClassGroupContext<T>, (x, n): (&Self, &T::Int)| {
+            let mut r = Self::default();
+            Self::nupow_into(ctx, &mut r, x, n);
+            r
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            bases.par_iter().zip(scalars).map_init(ClassGroupContext::new, mul).collect()
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            let mut ctx = ClassGroupContext::new();
+            bases.iter().zip(scalars).map(|xn| mul(&mut ctx, xn)).collect()
+        }
+    }
+
+    fn batch_reduce(elems: &mut [Self]) {
+        #[cfg(feature = "parallel")]
+        {
+            elems.par_iter_mut().for_each_init(ClassGroupContext::new, |ctx, f| f.reduce_in_place(ctx));
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            let mut ctx = ClassGroupContext::new();
+            elems.iter_mut().for_each(|f| f.reduce_in_place(&mut ctx));
+        }
+    }
 }
 
-impl Display for ClassGroup<TestClassConfig> {
+impl<T: ClassConfig> Display for ClassGroup<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "CG({}, {}, {})", self.a, self.b, self.c)
     }
 }
 
-// Dist
-impl Distribution<ClassGroup<TestClassConfig>> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, _rng: &mut R) -> ClassGroup<TestClassConfig> {
-        unimplemented!();
+// Deserialization
+// Reads a, b and c like the derived impl would, then runs the checks of Valid if asked to
+impl<T: ClassConfig> CanonicalDeserialize for ClassGroup<T> {
+    fn deserialize_with_mode<R: ark_std::io::Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let a = T::Int::deserialize_with_mode(&mut reader, compress, validate)?;
+        let b = T::Int::deserialize_with_mode(&mut reader, compress, validate)?;
+        let c = T::Int::deserialize_with_mode(&mut reader, compress, validate)?;
+
+        let f = Self { a, b, c };
+        if validate == Validate::Yes {
+            f.check()?;
+        }
+        Ok(f)
+    }
+}
+
+// Only reduced, primitive forms of the discriminant of T are valid, which keeps forms of other
+// groups out, as well as non-primitive ones that could have small order
+impl<T: ClassConfig> Valid for ClassGroup<T> {
+    fn check(&self) -> Result<(), SerializationError> {
+        if self.check_form().is_err() || !self.is_reduced() {
+            return Err(SerializationError::InvalidData);
+        }
+        Ok(())
+    }
+
+    fn batch_check<'a>(batch: impl Iterator<Item = &'a Self> + Send) -> Result<(), SerializationError>
+    where
+        Self: 'a,
+    {
+        #[cfg(feature = "parallel")]
+        {
+            batch.par_bridge().try_for_each(|f| f.check())
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            for f in batch {
+                f.check()?;
+            }
+            Ok(())
+        }
     }
 }
 
 // Constants
-impl Zero for ClassGroup<TestClassConfig> {
+impl<T: ClassConfig> Zero for ClassGroup<T> {
     fn zero() -> Self {
-        let a = <TestClassConfig as ClassConfig>::Int::one();
-        let b = <TestClassConfig as ClassConfig>::Int::one();
-        let c = (<TestClassConfig as ClassConfig>::Int::one() - TestClassConfig::discriminant()) >> 2;
-        Self { a, b, c }
+        Self::principal(&T::discriminant())
     }
 
     fn is_zero(&self) -> bool {
-        let a_check = self.a.is_one();
-        let b_check = self.b.is_one();
-        let c_check = self.c == ((<TestClassConfig as ClassConfig>::Int::one() - TestClassConfig::discriminant()) >> 2);
-        a_check && b_check && c_check
+        *self == Self::principal(&T::discriminant())
     }
 }
 
-impl Zeroize for ClassGroup<TestClassConfig> {
+impl<T: ClassConfig> Zeroize for ClassGroup<T> {
     fn zeroize(&mut self) {
-        unimplemented!();
+        self.a.zeroize();
+        self.b.zeroize();
+        self.c.zeroize();
     }
 }
 
 // Ops
-impl Neg for ClassGroup<TestClassConfig> {
+// (a, -b, c), except for b = a or a = c where the reduced form is its own inverse
+impl<T: FormConfig> Neg for ClassGroup<T> {
     type Output = Self;
 
-    fn neg(self) -> Self::Output {
-        Self {
-            a: self.a,
-            b: -self.b,
-            c: self.c,
+    fn neg(mut self) -> Self::Output {
+        if self.b != self.a && self.a != self.c {
+            self.b.neg_in_place();
         }
+        self
     }
 }
 
 // Add
-impl Add for ClassGroup<TestClassConfig> {
+// The operators compose on the context of the thread, see with_thread_context, so only the result allocates
+impl<T: ClassConfig> Add for ClassGroup<T> {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
@@ -209,15 +1291,15 @@ impl Add for ClassGroup<TestClassConfig> {
     }
 }
 
-impl AddAssign for ClassGroup<TestClassConfig> {
+impl<T: ClassConfig> AddAssign for ClassGroup<T> {
     fn add_assign(&mut self, rhs: Self) {
         let mut r: Self = Default::default();
-        Self::nucomp(&mut r, &self, &rhs);
+        Self::nucomp(&mut r, self, &rhs);
         *self = r;
     }
 }
 
-impl<'a> Add<&'a Self> for ClassGroup<TestClassConfig> {
+impl<'a, T: ClassConfig> Add<&'a Self> for ClassGroup<T> {
     type Output = Self;
 
     fn add(self, rhs: &'a Self) -> Self::Output {
@@ -227,15 +1309,15 @@ impl<'a> Add<&'a Self> for ClassGroup<TestClassConfig> {
     }
 }
 
-impl<'a> AddAssign<&'a Self> for ClassGroup<TestClassConfig> {
+impl<'a, T: ClassConfig> AddAssign<&'a Self> for ClassGroup<T> {
     fn add_assign(&mut self, rhs: &'a Self) {
         let mut r: Self = Default::default();
-        Self::nucomp(&mut r, &self, rhs);
+        Self::nucomp(&mut r, self, rhs);
         *self = r;
     }
 }
 
-impl<'a> Add<&'a mut Self> for ClassGroup<TestClassConfig> {
+impl<'a, T: ClassConfig> Add<&'a mut Self> for ClassGroup<T> {
     type Output = Self;
 
     fn add(self, rhs: &'a mut Self) -> Self::Output {
@@ -245,135 +1327,140 @@ impl<'a> Add<&'a mut Self> for ClassGroup<TestClassConfig> {
     }
 }
 
-impl<'a> AddAssign<&'a mut Self> for ClassGroup<TestClassConfig> {
+impl<'a, T: ClassConfig> AddAssign<&'a mut Self> for ClassGroup<T> {
     fn add_assign(&mut self, rhs: &'a mut Self) {
         let mut r: Self = Default::default();
-        Self::nucomp(&mut r, &self, rhs);
+        Self::nucomp(&mut r, self, rhs);
         *self = r;
     }
 }
 
 // Sub
-impl Sub for ClassGroup<TestClassConfig> {
+impl<T: ClassConfig> Sub for ClassGroup<T> {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
         let mut r: Self = Default::default();
-        Self::nucomp(&mut r, &self, &rhs);
-        -r
+        Self::nucomp(&mut r, &self, &-rhs);
+        r
     }
 }
 
-impl SubAssign for ClassGroup<TestClassConfig> {
+impl<T: ClassConfig> SubAssign for ClassGroup<T> {
     fn sub_assign(&mut self, rhs: Self) {
         let mut r: Self = Default::default();
-        Self::nucomp(&mut r, &self, &rhs);
-        *self = -r;
+        Self::nucomp(&mut r, self, &-rhs);
+        *self = r;
     }
 }
 
-impl<'a> Sub<&'a Self> for ClassGroup<TestClassConfig> {
+impl<'a, T: ClassConfig> Sub<&'a Self> for ClassGroup<T> {
     type Output = Self;
 
     fn sub(self, rhs: &'a Self) -> Self::Output {
         let mut r: Self = Default::default();
-        Self::nucomp(&mut r, &self, rhs);
-        -r
+        Self::nucomp(&mut r, &self, &-rhs);
+        r
     }
 }
 
-impl<'a> SubAssign<&'a Self> for ClassGroup<TestClassConfig> {
+impl<'a, T: ClassConfig> SubAssign<&'a Self> for ClassGroup<T> {
     fn sub_assign(&mut self, rhs: &'a Self) {
         let mut r: Self = Default::default();
-        Self::nucomp(&mut r, &self, rhs);
-        *self = -r;
+        Self::nucomp(&mut r, self, &-rhs);
+        *self = r;
     }
 }
 
-impl<'a> Sub<&'a mut Self> for ClassGroup<TestClassConfig> {
+impl<'a, T: ClassConfig> Sub<&'a mut Self> for ClassGroup<T> {
     type Output = Self;
 
     fn sub(self, rhs: &'a mut Self) -> Self::Output {
         let mut r: Self = Default::default();
-        Self::nucomp(&mut r, &self, rhs);
-        -r
+        Self::nucomp(&mut r, &self, &-&*rhs);
+        r
     }
 }
 
-impl<'a> SubAssign<&'a mut Self> for ClassGroup<TestClassConfig> {
+impl<'a, T: ClassConfig> SubAssign<&'a mut Self> for ClassGroup<T> {
     fn sub_assign(&mut self, rhs: &'a mut Self) {
         let mut r: Self = Default::default();
-        Self::nucomp(&mut r, &self, rhs);
-        *self = -r;
+        Self::nucomp(&mut r, self, &-&*rhs);
+        *self = r;
     }
 }
 
-// Mul by ZZ
-impl Mul<<TestClassConfig as ClassConfig>::Int> for ClassGroup<TestClassConfig> {
+// Mul by the config integer
+// A single impl over Borrow covers Int, &Int and &mut Int, since T::Int could itself be a reference
+impl<T: ClassConfig, S: Borrow<T::Int>> Mul<S> for ClassGroup<T> {
     type Output = Self;
 
-    fn mul(self, rhs: <TestClassConfig as ClassConfig>::Int) -> Self::Output {
+    fn mul(self, rhs: S) -> Self::Output {
         let mut r: Self = Default::default();
-        Self::nupow(&mut r, &self, &rhs);
+        Self::nupow(&mut r, &self, rhs.borrow());
         r
     }
 }
 
-impl MulAssign<<TestClassConfig as ClassConfig>::Int> for ClassGroup<TestClassConfig> {
-    fn mul_assign(&mut self, rhs: <TestClassConfig as ClassConfig>::Int) {
+impl<T: ClassConfig, S: Borrow<T::Int>> MulAssign<S> for ClassGroup<T> {
+    fn mul_assign(&mut self, rhs: S) {
         let mut r: Self = Default::default();
-        Self::nupow(&mut r, &self, &rhs);
+        Self::nupow(&mut r, self, rhs.borrow());
         *self = r;
     }
 }
 
-impl<'a> Mul<&'a <TestClassConfig as ClassConfig>::Int> for ClassGroup<TestClassConfig> {
-    type Output = Self;
+// Ops on references
+impl<T: ClassConfig> Neg for &ClassGroup<T> {
+    type Output = ClassGroup<T>;
 
-    fn mul(self, rhs: &'a <TestClassConfig as ClassConfig>::Int) -> Self::Output {
-        let mut r: Self = Default::default();
-        Self::nupow(&mut r, &self, rhs);
-        r
+    fn neg(self) -> Self::Output {
+        -self.clone()
     }
 }
 
-impl<'a> MulAssign<&'a <TestClassConfig as ClassConfig>::Int> for ClassGroup<TestClassConfig> {
-    fn mul_assign(&mut self, rhs: &'a <TestClassConfig as ClassConfig>::Int) {
-        let mut r: Self = Default::default();
-        Self::nupow(&mut r, &self, rhs);
-        *self = r;
+impl<T: ClassConfig> Add<&ClassGroup<T>> for &ClassGroup<T> {
+    type Output = ClassGroup<T>;
+
+    fn add(self, rhs: &ClassGroup<T>) -> Self::Output {
+        let mut r: ClassGroup<T> = Default::default();
+        ClassGroup::nucomp(&mut r, self, rhs);
+        r
     }
 }
 
-impl<'a> Mul<&'a mut <TestClassConfig as ClassConfig>::Int> for ClassGroup<TestClassConfig> {
-    type Output = Self;
+impl<T: ClassConfig> Sub<&ClassGroup<T>> for &ClassGroup<T> {
+    type Output = ClassGroup<T>;
 
-    fn mul(self, rhs: &'a mut <TestClassConfig as ClassConfig>::Int) -> Self::Output {
-        let mut r: Self = Default::default();
-        Self::nupow(&mut r, &self, rhs);
+    fn sub(self, rhs: &ClassGroup<T>) -> Self::Output {
+        let mut r: ClassGroup<T> = Default::default();
+        ClassGroup::nucomp(&mut r, self, &-rhs);
         r
     }
 }
-impl<'a> MulAssign<&'a mut <TestClassConfig as ClassConfig>::Int> for ClassGroup<TestClassConfig> {
-    fn mul_assign(&mut self, rhs: &'a mut <TestClassConfig as ClassConfig>::Int) {
-        let mut r: Self = Default::default();
-        Self::nupow(&mut r, &self, rhs);
-        *self = r;
+
+impl<T: ClassConfig, S: Borrow<T::Int>> Mul<S> for &ClassGroup<T> {
+    type Output = ClassGroup<T>;
+
+    fn mul(self, rhs: S) -> Self::Output {
+        let mut r: ClassGroup<T> = Default::default();
+        ClassGroup::nupow(&mut r, self, rhs.borrow());
+        r
     }
 }
 
-impl Sum for ClassGroup<TestClassConfig> {
+impl<T: ClassConfig> Sum for ClassGroup<T> {
     fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
-        let mut r: Self = Default::default();
+        let mut r = Self::zero();
         for i in iter {
             r += i;
         }
         r
     }
 }
-impl<'a> Sum<&'a ClassGroup<TestClassConfig>> for ClassGroup<TestClassConfig> {
+impl<'a, T: ClassConfig> Sum<&'a ClassGroup<T>> for ClassGroup<T> {
     fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
-        let mut r: Self = Default::default();
+        let mut r = Self::zero();
         for i in iter {
             r += i;
         }
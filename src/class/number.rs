@@ -0,0 +1,327 @@
+use std::{f64::consts::PI, fmt::Display};
+
+use ark_std::{One, Zero};
+use rug::Integer as RugInteger;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::class::{
+    config::FormConfig, context::ClassGroupContext, order::BabySteps, structure::StructureError, ClassGroup,
+};
+use crate::integer::{factor::gcd, ZZ};
+use crate::Integer;
+
+// Below this |D| the class number is counted from the reduced forms
+pub(super) const COUNT_FORMS_BOUND: i64 = 1 << 24;
+
+// Primes of the truncated Euler product of L(1, chi_D)
+const EULER_PRODUCT_BOUND: usize = 1 << 22;
+
+// Half width of the search interval in standard deviations of the truncated Euler product,
+// whose relative error behaves like 1 / sqrt(Q ln Q) for primes up to Q
+const ESTIMATE_DEVIATIONS: f64 = 8.0;
+
+// Giant steps per parallel task
+const GIANT_CHUNK: u64 = 1 << 12;
+
+// Baby steps of the search, about 128 MiB of table. Wider intervals take more giant steps instead.
+const MAX_BABY_STEPS: u64 = 1 << 22;
+
+// Giant steps of one search, a minute or two of compositions on one core at 118 bits
+const MAX_GIANT_STEPS: u64 = 1 << 26;
+
+// Prime forms in a row that must leave the exponent unchanged before giving up
+const STALL_PRIMES: usize = 64;
+
+// Prime forms in a row whose order must divide e before its single multiple is taken as h(D)
+const CHECK_PRIMES: usize = 16;
+
+// Why class_number could not determine h(D)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ClassNumberError {
+    // D is not negative, or not 0 or 1 mod 4
+    InvalidDiscriminant,
+    // The search interval needs more than MAX_BABY_STEPS baby steps and MAX_GIANT_STEPS giant steps
+    IntervalTooWide,
+    // The orders of STALL_PRIMES prime forms in a row left the exponent unchanged with more than one
+    // multiple of it in twice the interval, which takes a class group of high rank
+    ExponentTooSmall,
+    // ClassGroupStructure, which ClassConfig::class_number falls back to, failed
+    Structure(StructureError),
+}
+
+impl Display for ClassNumberError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidDiscriminant => write!(f, "discriminant must be negative and 0 or 1 mod 4"),
+            Self::IntervalTooWide => write!(f, "class number search interval too wide"),
+            Self::ExponentTooSmall => write!(f, "class group exponent too small to determine h(D)"),
+            Self::Structure(e) => write!(f, "class group structure: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ClassNumberError {}
+
+// Class number h(D) of a negative discriminant D. Counts reduced forms for small |D|, otherwise
+// Shanks-Mestre: the analytic class number formula with a truncated Euler product of L(1, chi_D)
+// gives an interval around h(D), then baby-step giant-step finds the multiples in it of the orders
+// of prime forms until the lcm e of those orders has a single multiple in the interval. Giant
+// steps are spread over threads with the parallel feature.
+//
+// The interval is a heuristic one of ESTIMATE_DEVIATIONS standard deviations, so the multiple of e
+// must also be the only one in twice the interval, and the orders of CHECK_PRIMES more prime forms
+// must divide e, before it is returned. Fails with ExponentTooSmall if e stops growing before that,
+// which takes a class group of high rank. The first search covers about h(D) / 500 multiples with
+// at most MAX_BABY_STEPS baby steps, so memory stays at about 128 MiB, and the time, about 10 s for
+// 100 bits in release builds, grows linearly beyond 2^44 multiples at about 110 bits of D, up to
+// IntervalTooWide at about 118 bits. ClassConfig::class_number falls back to ClassGroupStructure on
+// either error.
+pub fn class_number(disc: &ZZ) -> Result<ZZ, ClassNumberError> {
+    if *disc >= ZZ::zero() || disc.rem_floor(&ZZ::from(4u64)) > ZZ::one() {
+        return Err(ClassNumberError::InvalidDiscriminant);
+    }
+
+    if *disc > ZZ::from(-COUNT_FORMS_BOUND) {
+        return Ok(ZZ::from(count_reduced_forms(i64::from(disc.clone()))));
+    }
+
+    let disc = Discriminant::new(disc);
+    let ctx = &mut disc.context();
+    let (estimate, mut radius) = estimate(&disc.d);
+    let mut e = ZZ::one();
+    let mut stall = 0;
+    let mut p = 1u64;
+    loop {
+        let lo = (estimate.clone() - &radius).max(ZZ::one());
+        let hi = estimate.clone() + &radius;
+
+        // Multiples of e in [lo, hi], and whether there is just one in twice the interval
+        let k0 = ceil_div(&lo, &e);
+        let k1 = floor_div(&hi, &e);
+        let single = k0 == k1 && {
+            let wide = ZZ::from(2u64) * &radius;
+            let lo = (estimate.clone() - &wide).max(ZZ::one());
+            floor_div(&(estimate.clone() + &wide), &e) == ceil_div(&lo, &e)
+        };
+        if single && stall >= CHECK_PRIMES {
+            return Ok(e * &k0);
+        }
+        if stall >= STALL_PRIMES {
+            return Err(ClassNumberError::ExponentTooSmall);
+        }
+
+        p += 1;
+        let Some(g) = disc.prime_form(ctx, p) else {
+            continue;
+        };
+
+        let y = disc.pow(ctx, &g, &e);
+        let k = if y.is_reduced_principal() {
+            Some(ZZ::one())
+        } else if k0 > k1 {
+            None
+        } else {
+            find_multiple(&disc, &y, &k0, &k1)?
+        };
+
+        // h(D) lies outside the interval, widen it
+        let Some(k) = k else {
+            radius *= ZZ::from(2u64);
+            stall = 0;
+            continue;
+        };
+
+        // e <- lcm(e, ord(g))
+        let n = g.order_from_multiple_in(ctx, &(e.clone() * &k));
+        let mut grow = n.clone();
+        grow.div_exact(&gcd(&e, &n));
+        if grow.is_one() {
+            stall += 1;
+        } else {
+            e *= grow;
+            stall = 0;
+        }
+    }
+}
+
+// Forms of class_number. Their discriminant is only known at runtime, so their config only fixes the
+// coefficients, D and the NUCOMP bound are carried in Discriminant and the contexts built from it.
+type Form = ClassGroup<RuntimeFormConfig>;
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
+struct RuntimeFormConfig;
+
+impl FormConfig for RuntimeFormConfig {
+    type Int = ZZ;
+}
+
+// Discriminant of a class_number call and its NUCOMP bound |D/4|^(1/4)
+struct Discriminant {
+    d: ZZ,
+    bound: ZZ,
+}
+
+impl Discriminant {
+    fn new(d: &ZZ) -> Self {
+        let bound = ZZ { value: (d.value.clone().abs() >> 2u32).root(4) };
+        Self { d: d.clone(), bound }
+    }
+
+    // Context for the group operations on forms of discriminant d
+    fn context(&self) -> ClassGroupContext<RuntimeFormConfig> {
+        ClassGroupContext::with_bound(self.bound.clone())
+    }
+
+    // Principal form of D
+    fn identity(&self) -> Form {
+        Form::principal(&self.d)
+    }
+
+    // Reduced prime form of p, None unless p is a prime with (D / p) = 1
+    fn prime_form(&self, ctx: &mut ClassGroupContext<RuntimeFormConfig>, p: u64) -> Option<Form> {
+        let mut f = Form::prime_form_unreduced_of(&self.d, ZZ::from(p)).ok()?;
+        f.reduce_in_place(ctx);
+        Some(f)
+    }
+
+    // f^n
+    fn pow(&self, ctx: &mut ClassGroupContext<RuntimeFormConfig>, f: &Form, n: &ZZ) -> Form {
+        let mut r = Form::default();
+        Form::nupow_into(ctx, &mut r, f, n);
+        r
+    }
+
+}
+
+// Number of reduced primitive forms of discriminant d
+pub(super) fn count_reduced_forms(d: i64) -> u64 {
+    let gcd = |mut x: i64, mut y: i64| {
+        while y != 0 {
+            (x, y) = (y, x % y);
+        }
+        x.abs()
+    };
+
+    let mut h = 0;
+    let mut a = 1i64;
+    while 3 * a * a <= -d {
+        // b = d mod 2 and |b| <= a, counted for both signs where (a, -b, c) is also reduced
+        let mut b = d & 1;
+        while b <= a {
+            let t = b * b - d;
+            if t % (4 * a) == 0 {
+                let c = t / (4 * a);
+                if c >= a && gcd(gcd(a, b), c) == 1 {
+                    h += if b == 0 || b == a || a == c { 1 } else { 2 };
+                }
+            }
+            b += 2;
+        }
+        a += 1;
+    }
+    h
+}
+
+// Estimate of h(D) from the truncated Euler product and the radius of the search interval
+pub(super) fn estimate(disc: &ZZ) -> (ZZ, ZZ) {
+    let mut composite = vec![false; EULER_PRODUCT_BOUND];
+    let mut l = 1f64;
+    for p in 2..EULER_PRODUCT_BOUND {
+        if composite[p] {
+            continue;
+        }
+        for q in (p * p..EULER_PRODUCT_BOUND).step_by(p) {
+            composite[q] = true;
+        }
+        let chi = disc.kronecker(&ZZ::from(p)) as f64;
+        l /= 1.0 - chi / p as f64;
+    }
+
+    // h = w sqrt|D| L(1, chi_D) / (2 pi), with w units
+    let w = match disc.value.to_i64() {
+        Some(-3) => 6.0,
+        Some(-4) => 4.0,
+        _ => 2.0,
+    };
+    let h = w * disc.value.to_f64().abs().sqrt() * l / (2.0 * PI);
+    let q = EULER_PRODUCT_BOUND as f64;
+    let radius = h * ESTIMATE_DEVIATIONS / (q * q.ln()).sqrt();
+
+    let to_zz = |x: f64| ZZ { value: RugInteger::from_f64(x.round()).unwrap() };
+    (to_zz(h), to_zz(radius.max(1.0)))
+}
+
+fn floor_div(x: &ZZ, y: &ZZ) -> ZZ {
+    let mut q = x.clone();
+    let mut r = y.clone();
+    q.div_rem_floor_mut(&mut r);
+    q
+}
+
+fn ceil_div(x: &ZZ, y: &ZZ) -> ZZ {
+    let mut q = x.clone();
+    let mut r = y.clone();
+    q.div_rem_ceil_mut(&mut r);
+    q
+}
+
+// Some k > 0 with y^k = 1 found by baby-step giant-step over [k0, k1], None if there is none
+// in the interval. Matches just above k1 may be returned as well. Fails if the interval needs more
+// than MAX_GIANT_STEPS giant steps with MAX_BABY_STEPS baby steps.
+fn find_multiple(disc: &Discriminant, y: &Form, k0: &ZZ, k1: &ZZ) -> Result<Option<ZZ>, ClassNumberError> {
+    let width = (k1.clone() - k0).value.to_u64().and_then(|w| w.checked_add(1));
+    let width = width.ok_or(ClassNumberError::IntervalTooWide)?;
+    let m = (width.isqrt() + 1).min(MAX_BABY_STEPS);
+    let steps = width.div_ceil(m);
+    if steps > MAX_GIANT_STEPS {
+        return Err(ClassNumberError::IntervalTooWide);
+    }
+
+    // y^j for j in [0, m), up to the order of y after which they repeat, then giant steps y^-(k0 + i m)
+    let mut baby = BabySteps::with_capacity(m as usize);
+    let mut b = disc.identity();
+    let mut t = Form::default();
+    let ctx = &mut disc.context();
+    let mut cycled = false;
+    for j in 0..m {
+        if !cycled {
+            baby.insert(&b, j);
+        }
+        Form::nucomp_into(ctx, &mut t, &b, y);
+        std::mem::swap(&mut b, &mut t);
+        cycled |= b.is_reduced_principal();
+    }
+    let step = -b;
+
+    let search = |chunk: u64| {
+        let i0 = chunk * GIANT_CHUNK;
+        let ctx = &mut disc.context();
+        let mut z = -disc.pow(ctx, y, &(k0.clone() + ZZ::from(i0) * &ZZ::from(m)));
+        let mut t = Form::default();
+        for i in i0..(i0 + GIANT_CHUNK).min(steps) {
+            for j in baby.candidates(&z) {
+                let k = k0.clone() + ZZ::from(i) * &ZZ::from(m) + ZZ::from(j);
+                if k > ZZ::zero() && disc.pow(ctx, y, &k).is_reduced_principal() {
+                    return Some(k);
+                }
+            }
+            Form::nucomp_into(ctx, &mut t, &z, &step);
+            std::mem::swap(&mut z, &mut t);
+        }
+        None
+    };
+
+    let chunks = steps.div_ceil(GIANT_CHUNK);
+
+    #[cfg(feature = "parallel")]
+    {
+        Ok((0..chunks).into_par_iter().find_map_any(search))
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        Ok((0..chunks).find_map(search))
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use ark_std::{One, Zero};
+
+use crate::class::{
+    config::{ClassConfig, FormConfig},
+    context::{with_thread_context, ClassGroupContext},
+    ClassGroup,
+};
+use crate::integer::factor::factor;
+use crate::Integer;
+
+// Baby-step table, keyed by the low 64 bits of (a, b). Distinct forms can share a key from about
+// 120-bit discriminants on, so a lookup returns every j stored under the key and the caller confirms
+// each. The first j of a key is kept in the map, the rare further ones in collisions.
+#[derive(Default)]
+pub(super) struct BabySteps {
+    first: HashMap<(i64, i64), u64>,
+    collisions: HashMap<(i64, i64), Vec<u64>>,
+}
+
+impl BabySteps {
+    pub(super) fn with_capacity(n: usize) -> Self {
+        Self { first: HashMap::with_capacity(n), collisions: HashMap::new() }
+    }
+
+    fn key<T: FormConfig>(f: &ClassGroup<T>) -> (i64, i64) {
+        (f.a.shr_to_i64_wrapping(0), f.b.shr_to_i64_wrapping(0))
+    }
+
+    pub(super) fn insert<T: FormConfig>(&mut self, f: &ClassGroup<T>, j: u64) {
+        let key = Self::key(f);
+        match self.first.entry(key) {
+            Entry::Vacant(e) => {
+                e.insert(j);
+            }
+            Entry::Occupied(_) => self.collisions.entry(key).or_default().push(j),
+        }
+    }
+
+    // The j stored under the key of f, in the order they were inserted
+    pub(super) fn candidates<T: FormConfig>(&self, f: &ClassGroup<T>) -> impl Iterator<Item = u64> + '_ {
+        let key = Self::key(f);
+        self.first.get(&key).into_iter().chain(self.collisions.get(&key).into_iter().flatten()).copied()
+    }
+}
+
+impl<T: ClassConfig> ClassGroup<T> {
+    // Exact order of self. With a known multiple of the order, e.g. the class number, the prime
+    // factors of the multiple are stripped as long as the power stays the identity. Otherwise
+    // Terr's baby-step giant-step, which needs about 2 sqrt(2 n) compositions for order n, and as
+    // much memory for the baby steps, so it is meant for discriminants up to about 80 bits.
+    pub fn order(&self, multiple: Option<&T::Int>) -> T::Int {
+        match multiple {
+            Some(m) => self.order_from_multiple(m),
+            None => self.order_bsgs(),
+        }
+    }
+
+    fn order_from_multiple(&self, multiple: &T::Int) -> T::Int {
+        with_thread_context(|ctx| self.order_from_multiple_in(ctx, multiple))
+    }
+
+    // Terr: baby steps g^j are stored for j = 1..=i, giant steps are g^(i (i + 1) / 2), so step i
+    // covers the orders in [i (i - 1) / 2, i (i + 1) / 2] and the first match is the order itself.
+    fn order_bsgs(&self) -> T::Int {
+        if self.is_identity() {
+            return T::Int::one();
+        }
+
+        let mut baby = BabySteps::default();
+        let mut b = Self::zero();
+        let mut giant = Self::zero();
+        let mut t = Self::default();
+        for i in 1u64.. {
+            Self::nucomp(&mut t, &b, self);
+            std::mem::swap(&mut b, &mut t);
+            if b.is_identity() {
+                return T::Int::from(i);
+            }
+            baby.insert(&b, i);
+
+            // A match with a baby step g^j that is not the giant step itself fails the check, any
+            // other candidate j of the key is still tried. Multiples of the order from step i on are
+            // above i (i + 1) / 2, so a confirmed n is the order.
+            Self::nucomp(&mut t, &giant, &b);
+            std::mem::swap(&mut giant, &mut t);
+            let triangle = i * (i + 1) / 2;
+            if giant.is_identity() {
+                return T::Int::from(triangle);
+            }
+            for j in baby.candidates(&giant).filter(|&j| j < triangle) {
+                let n = T::Int::from(triangle - j);
+                if (self.clone() * &n).is_identity() {
+                    return n;
+                }
+            }
+        }
+        unreachable!()
+    }
+}
+
+impl<T: FormConfig> ClassGroup<T> {
+    // Order of a reduced form from a multiple of it, with the powers taken on ctx, so that it also
+    // serves forms whose discriminant is only known at runtime, see class::number
+    pub(crate) fn order_from_multiple_in(&self, ctx: &mut ClassGroupContext<T>, multiple: &T::Int) -> T::Int {
+        let mut power = |n: &T::Int| {
+            let mut r = Self::default();
+            Self::nupow_into(ctx, &mut r, self, n);
+            r.is_reduced_principal()
+        };
+
+        let mut n = multiple.abs();
+        assert!(!n.is_zero() && power(&n), "not a multiple of the order");
+
+        for (q, e) in factor(&n) {
+            for _ in 0..e {
+                let mut m = n.clone();
+                m.div_exact(&q);
+                if !power(&m) {
+                    break;
+                }
+                n = m;
+            }
+        }
+        n
+    }
+}
@@ -0,0 +1,66 @@
+use ark_std::rand::{distributions::Standard, prelude::Distribution, Rng};
+
+use crate::class::{config::ClassConfig, ClassGroup};
+use crate::{msm::VariableBaseMSM, Integer};
+
+// Number of prime forms multiplied together by SamplingStrategy::PrimeProduct
+pub const SAMPLING_PRIME_FORMS: usize = 8;
+
+// Statistical margin added to the class number estimate for the sampling exponents
+pub const SAMPLING_MARGIN_BITS: u32 = 128;
+
+// How random elements are drawn, see ClassConfig::sampling_strategy
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum SamplingStrategy {
+    // prod_i p_i^e_i over the first SAMPLING_PRIME_FORMS prime forms, with independent exponents
+    // below 2^sampling_exponent_bits. Close to uniform on the subgroup they generate, which is
+    // usually the whole group.
+    #[default]
+    PrimeProduct,
+    // g^e for g = ClassConfig::generator() and an exponent below 2^sampling_exponent_bits.
+    // Close to uniform on the cyclic subgroup generated by g, and cheaper.
+    GeneratorExponent,
+}
+
+impl<T: ClassConfig> ClassGroup<T> {
+    // Random element drawn with the given strategy, reproducible for a seeded rng
+    pub fn sample_with<R: Rng + ?Sized>(rng: &mut R, strategy: SamplingStrategy) -> Self {
+        let bits = T::sampling_exponent_bits();
+        let forms = match strategy {
+            SamplingStrategy::PrimeProduct => small_prime_forms::<T>(SAMPLING_PRIME_FORMS),
+            SamplingStrategy::GeneratorExponent => vec![T::generator()],
+        };
+        let exponents: Vec<T::Int> = forms.iter().map(|_| random_bits(bits, rng)).collect();
+        Self::msm(&forms, &exponents)
+    }
+}
+
+impl<T: ClassConfig> Distribution<ClassGroup<T>> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> ClassGroup<T> {
+        ClassGroup::sample_with(rng, T::sampling_strategy())
+    }
+}
+
+// Uniform integer in [0, 2^bits)
+pub(crate) fn random_bits<I: Integer, R: Rng + ?Sized>(bits: u32, rng: &mut R) -> I {
+    let limb = I::from(1u128 << 64);
+    let words = bits.div_ceil(64);
+    let mut n = I::zero();
+    for _ in 0..words {
+        n = n * &limb + I::from(rng.next_u64());
+    }
+    n >> (64 * words - bits)
+}
+
+// Prime forms of the first k primes p with (D / p) = 1
+pub(crate) fn small_prime_forms<T: ClassConfig>(k: usize) -> Vec<ClassGroup<T>> {
+    let mut forms = Vec::with_capacity(k);
+    let mut p = 1u64;
+    while forms.len() < k {
+        p += 1;
+        if let Ok(f) = ClassGroup::prime_form(T::Int::from(p)) {
+            forms.push(f);
+        }
+    }
+    forms
+}
@@ -0,0 +1,1410 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fmt::Display,
+    rc::Rc,
+};
+
+use ark_serialize::CanonicalSerialize;
+use ark_std::{rand::Rng, One, Zero};
+
+use crate::class::{
+    config::ClassConfig,
+    hash::discriminant_bytes,
+    number::{count_reduced_forms, estimate, COUNT_FORMS_BOUND},
+    ClassGroup,
+};
+use crate::integer::{factor::gcd, ZZ};
+use crate::{msm::VariableBaseMSM, shake::Shake256, Integer};
+
+// Default factor base bound exp(FACTOR_BASE_EXPONENT sqrt(ln|D| ln ln|D|))
+const FACTOR_BASE_EXPONENT: f64 = 0.4;
+
+// Half width M of the interval [-M, M] sieved per polynomial, |D|^(1/4) clamped to this range
+const SIEVE_MIN_HALF_WIDTH: i64 = 1 << 6;
+const SIEVE_MAX_HALF_WIDTH: i64 = 1 << 15;
+
+// Positions sharing a threshold, taken at the smallest polynomial value of the block
+const SIEVE_BLOCK: usize = 256;
+
+// Primes below this are not sieved but only found by trial division
+const SIEVE_MIN_PRIME: u32 = 32;
+
+// Bits the sieved logarithms may fall short of log2 of the polynomial value for a position to be
+// checked, besides the large prime, for the primes that are not sieved, prime powers and the rounding
+// of the logarithms
+const SIEVE_SLACK_BITS: f64 = 14.0;
+
+// Values that are smooth but for one prime below LARGE_PRIME_FACTOR times the largest factor base prime
+// are kept as partial relations, two with the same large prime combine into a relation
+const LARGE_PRIME_FACTOR: u64 = 64;
+
+// The primes of the a of a polynomial are drawn from the pool primes within this factor of
+// their ideal size, so that the columns of no few primes fill up with relations
+const POLYNOMIAL_WINDOW: f64 = 1.65;
+
+// The last prime of a polynomial is picked at random among this many closing the gap to the best a
+const LAST_PRIME_CHOICES: usize = 4;
+
+// Factor bases with fewer odd split primes are too small for smooth values, relations then come from the
+// random walk from products of RELATION_FORMS random powers of absolute value at most RELATION_EXPONENT
+const SIEVE_MIN_POOL: usize = 32;
+const RELATION_FORMS: usize = 4;
+const RELATION_EXPONENT: i64 = 64;
+
+// Polynomials in a row without a relation after which the search gives up
+const POLYNOMIAL_LIMIT: usize = 1 << 16;
+
+// Polynomials tried for the prime ideal of a prime beyond the factor base
+const PRIME_POLYNOMIALS: usize = 1 << 10;
+
+// Random walk steps after which an element counts as not generated by the factor base
+const DECOMPOSE_STEPS: usize = 1 << 20;
+
+// Relations collected beyond the size of the factor base for the elimination
+const RELATION_EXCESS: usize = 20;
+
+// Relations added per round, also the relations the determinant must survive unchanged to be accepted
+const RELATION_BATCH: usize = 20;
+
+// Needing more than RELATION_LIMIT_FACTOR (n + RELATION_BATCH) relations for n factor base primes means
+// the search does not converge
+const RELATION_LIMIT_FACTOR: usize = 10;
+
+// Rows with more nonzero entries are not used as pivots, which limits the fill-in of the elimination
+const PIVOT_MAX_WEIGHT: usize = 256;
+
+// Why ClassGroupStructure could not be computed, or an element has no coordinates
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum StructureError {
+    // The factor base bound of compute_with_bound is not below 2^31
+    BoundTooLarge,
+    // No prime up to the factor base bound splits, or ramifies without dividing the conductor
+    EmptyFactorBase,
+    // With verify, the prime form of this prime up to Bach's bound was not found in the group generated
+    // by the factor base
+    NotGenerated(u32),
+    // The relation search stopped before det L came down to the bound of h(D)
+    RelationsExhausted,
+    // The element passed to coordinates was not decomposed over the factor base
+    NoDecomposition,
+}
+
+impl Display for StructureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BoundTooLarge => write!(f, "factor base bound must be below 2^31"),
+            Self::EmptyFactorBase => write!(f, "empty factor base"),
+            Self::NotGenerated(p) => write!(f, "prime form of {p} not generated by the factor base"),
+            Self::RelationsExhausted => write!(f, "no further class group relations found"),
+            Self::NoDecomposition => write!(f, "form not decomposed over the factor base"),
+        }
+    }
+}
+
+impl std::error::Error for StructureError {}
+
+// Relation as its nonzero entries (column, coefficient), sorted by column
+type Relation = Vec<(usize, i64)>;
+
+// Exponents over the factor base, with the large prime P beyond it and the b mod P of its prime
+// ideal for the ones that are smooth but for P
+type Partial = (Relation, Option<(u32, u32)>);
+
+// Prime ideal over p as the form (p, b, c), told apart from its inverse by b mod 2p
+#[derive(Clone, Debug)]
+struct FactorBasePrime<T: ClassConfig<Int = ZZ>> {
+    p: u32,
+    // b mod 2p
+    b: u32,
+    ramified: bool,
+    form: ClassGroup<T>,
+}
+
+impl<T: ClassConfig<Int = ZZ>> FactorBasePrime<T> {
+    // Split primes, and ramified ones that do not divide the conductor
+    fn new(p: u32) -> Option<Self> {
+        let disc = T::discriminant();
+        let p = ZZ::from(p);
+        let two_p = ZZ::from(2u64) * &p;
+        let four_p = ZZ::from(2u64) * &two_p;
+
+        let (f, ramified): (ClassGroup<T>, bool) = match disc.kronecker(&p) {
+            1 => (ClassGroup::prime_form_unreduced(p.clone()).ok()?, false),
+            0 => {
+                // b = 0 or p for odd p, b = 0 or 2 for p = 2, with b^2 = D mod 4p
+                let b = [ZZ::zero(), p.clone()]
+                    .into_iter()
+                    .chain([two_p.clone()])
+                    .find(|b| (b.clone() * b - &disc).is_divisible(&four_p))?;
+                let mut c = b.clone() * &b - &disc;
+                c.div_exact(&four_p);
+                if c.is_divisible(&p) {
+                    return None;
+                }
+                (ClassGroup::try_new(p.clone(), b, c).ok()?, true)
+            }
+            _ => return None,
+        };
+
+        let b = f.b.rem_floor(&two_p).value.to_u32().unwrap();
+        let mut form = f;
+        form.reduce();
+        Some(Self { p: p.value.to_u32().unwrap(), b, ramified, form })
+    }
+
+    // Exponent of this prime ideal in the form (a, b, c) for p^k || a, with the sign given by b mod 2p
+    fn exponent(&self, k: i64, b: &ZZ) -> i64 {
+        if self.ramified || b.value.mod_u(2 * self.p) == self.b {
+            k
+        } else {
+            -k
+        }
+    }
+}
+
+// Class group Cl(D) = Z/d_1 + ... + Z/d_r with d_1 | ... | d_r, computed from relations between the
+// prime forms of a factor base (Hafner-McCurley, Buchmann). Relations come from smooth values of the
+// polynomials of forms whose a is a product of factor base primes, found with a sieve, and span a
+// lattice L of Z^n with Cl(D) = Z^n / L once complete. They are sparse, so structured Gaussian
+// elimination first drops most columns without changing the quotient. On the dense rest, a multiple M of
+// the exponent of the group comes from the determinants of independent rows by CRT, elimination mod M
+// drops all but a few more columns, and the Smith normal form U L' V = diag(d_i) of the Hermite normal
+// form L' mod M of what is left gives the invariants, coordinates x V mod d_i for an exponent vector x,
+// and generators from the rows of V^-1.
+//
+// If the factor base generates Cl(D), which verify checks under GRH, then h(D) divides det L, so
+// det L = h(D) and L is complete once det L is below twice a lower bound of h(D). The lower bound is
+// exact below COUNT_FORMS_BOUND. Above, it is the estimate of class_number minus 8 standard deviations
+// of its truncated Euler product, a heuristic rather than a proven bound, so det L must also stay
+// unchanged over another RELATION_BATCH relations, which would most likely shrink a proper multiple.
+//
+// On one core in release builds a 160-bit discriminant takes about 2 s, a 180-bit one 15 s and a 200-bit
+// one 2.5 minutes, mostly sieving. With verify, which sieves for every prime up to Bach's bound, they
+// take about 5 s, 30 s and 3.5 minutes, see the tests.
+#[derive(Clone, Debug)]
+pub struct ClassGroupStructure<T: ClassConfig<Int = ZZ>> {
+    // Nontrivial invariants d_1 | ... | d_r
+    pub invariants: Vec<ZZ>,
+    // Generators of the cyclic factors, generators[i] of order invariants[i]
+    pub generators: Vec<ClassGroup<T>>,
+    sieve: Sieve<T>,
+    elimination: Elimination,
+    reduction: ModularElimination,
+    // Columns of V for the nontrivial invariants, reduced mod the invariant, one row per surviving column
+    coordinate_map: Vec<Vec<ZZ>>,
+}
+
+// Structured Gaussian elimination on sparse relations. A relation with coefficient +-1 in column j
+// expresses generator j through the others, and substituting it into the other relations drops the
+// column and the relation without changing the group, a Tietze transformation. The columns of large
+// primes are sparse and mostly go away, what remains is a small dense system. Runs on the first batch
+// of relations, later ones are only mapped to the surviving columns.
+#[derive(Clone, Debug)]
+struct Elimination {
+    // Eliminated columns in order, g_j = sum c_k g_k over the columns still alive at that point
+    substitutions: Vec<(usize, Relation)>,
+    // Surviving columns, increasing
+    columns: Vec<usize>,
+}
+
+fn coefficient(r: &Relation, j: usize) -> i64 {
+    r.binary_search_by_key(&j, |&(k, _)| k).map_or(0, |i| r[i].1)
+}
+
+// x + c y, None if a coefficient overflows
+fn add_multiple(x: &Relation, c: i64, y: &Relation) -> Option<Relation> {
+    let mut r = Vec::with_capacity(x.len() + y.len());
+    let (mut i, mut j) = (0, 0);
+    while i < x.len() || j < y.len() {
+        let (k, v) = match (x.get(i), y.get(j)) {
+            (Some(&(kx, vx)), Some(&(ky, _))) if kx < ky => {
+                i += 1;
+                (kx, vx)
+            }
+            (Some(&(kx, vx)), Some(&(ky, vy))) if kx == ky => {
+                i += 1;
+                j += 1;
+                (kx, vx.checked_add(c.checked_mul(vy)?)?)
+            }
+            (_, Some(&(ky, vy))) => {
+                j += 1;
+                (ky, c.checked_mul(vy)?)
+            }
+            (Some(&(kx, vx)), None) => {
+                i += 1;
+                (kx, vx)
+            }
+            (None, None) => unreachable!(),
+        };
+        if v != 0 {
+            r.push((k, v));
+        }
+    }
+    Some(r)
+}
+
+impl Elimination {
+    // Elimination on relations over n columns, and the relations that are left
+    fn new(n: usize, relations: &[Relation]) -> (Self, Vec<Relation>) {
+        let mut rows: Vec<Option<Relation>> = relations.iter().map(|r| Some(r.clone())).collect();
+        // Rows that may have a nonzero entry in each column
+        let mut occurs: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for (i, r) in relations.iter().enumerate() {
+            r.iter().for_each(|&(j, _)| occurs[j].push(i));
+        }
+        let mut alive = vec![true; n];
+        let mut substitutions = Vec::new();
+
+        let mut progress = true;
+        while progress {
+            progress = false;
+            // Large primes first, their columns are the sparsest
+            for j in (0..n).rev() {
+                if !alive[j] {
+                    continue;
+                }
+                occurs[j].retain(|&i| rows[i].as_ref().is_some_and(|r| coefficient(r, j) != 0));
+                occurs[j].sort_unstable();
+                occurs[j].dedup();
+
+                let weight = |i: usize| rows[i].as_ref().unwrap().len();
+                let pivot = occurs[j]
+                    .iter()
+                    .copied()
+                    .filter(|&i| coefficient(rows[i].as_ref().unwrap(), j).abs() == 1 && weight(i) <= PIVOT_MAX_WEIGHT)
+                    .min_by_key(|&i| weight(i));
+                let Some(p) = pivot else {
+                    continue;
+                };
+
+                // All rows or none, in case a coefficient overflows
+                let r = rows[p].as_ref().unwrap();
+                let sign = coefficient(r, j);
+                let updated: Option<Vec<(usize, Relation)>> = occurs[j]
+                    .iter()
+                    .filter(|&&i| i != p)
+                    .map(|&i| {
+                        let x = rows[i].as_ref().unwrap();
+                        add_multiple(x, -sign * coefficient(x, j), r).map(|x| (i, x))
+                    })
+                    .collect();
+                let Some(updated) = updated else {
+                    continue;
+                };
+
+                let r = rows[p].take().unwrap();
+                for (i, x) in updated {
+                    x.iter().for_each(|&(k, _)| occurs[k].push(i));
+                    rows[i] = (!x.is_empty()).then_some(x);
+                }
+                substitutions.push((j, r.iter().filter(|&&(k, _)| k != j).map(|&(k, c)| (k, -sign * c)).collect()));
+                alive[j] = false;
+                occurs[j].clear();
+                progress = true;
+            }
+        }
+
+        let columns = (0..n).filter(|&j| alive[j]).collect();
+        (Self { substitutions, columns }, rows.into_iter().flatten().collect())
+    }
+
+    // Exponent vector x over all columns as one over the surviving columns, substituting the
+    // eliminated generators in order
+    fn restrict(&self, mut x: Vec<ZZ>) -> Vec<ZZ> {
+        for (j, expression) in &self.substitutions {
+            if x[*j].is_zero() {
+                continue;
+            }
+            let xj = std::mem::replace(&mut x[*j], ZZ::zero());
+            for &(k, c) in expression {
+                x[k].add_mul(&xj, &ZZ::from(c));
+            }
+        }
+        self.columns.iter().map(|&j| std::mem::replace(&mut x[j], ZZ::zero())).collect()
+    }
+}
+
+fn dense(n: usize, r: &Relation) -> Vec<ZZ> {
+    let mut x = vec![ZZ::zero(); n];
+    r.iter().for_each(|&(k, c)| x[k] = ZZ::from(c));
+    x
+}
+
+// Gaussian elimination mod m on the dense relations Elimination leaves, for an m with m Z^n in the
+// lattice, so that every generator has order dividing m. A relation with a unit mod m in column j
+// expresses generator j through the others as in Elimination, and since the class group has few
+// invariants, only a few columns end up without a unit. Later relations are only mapped to the
+// surviving columns.
+#[derive(Clone, Debug)]
+struct ModularElimination {
+    modulus: ZZ,
+    // Eliminated columns in order, g_j = sum c_k g_k mod m over the columns still alive at that point
+    substitutions: Vec<(usize, Vec<(usize, ZZ)>)>,
+    // Surviving columns, increasing
+    columns: Vec<usize>,
+}
+
+impl ModularElimination {
+    // Elimination mod modulus on relations over n columns, and the relations that are left over the
+    // surviving columns
+    fn new(n: usize, mut rows: Vec<Vec<ZZ>>, modulus: ZZ) -> (Self, Vec<Vec<ZZ>>) {
+        // Room for the sums of products up front, growing entries one by one scatters them over the heap
+        let bits = 2 * modulus.significant_bits() as usize + 64;
+        rows.iter_mut().flatten().for_each(|x| x.value.reserve(bits));
+        let mut alive = vec![true; n];
+        let mut substitutions = Vec::new();
+
+        let mut progress = true;
+        while progress {
+            progress = false;
+            // Large primes first, so that the generators are small prime forms
+            for j in (0..n).rev() {
+                if !alive[j] {
+                    continue;
+                }
+                let unit = rows.iter_mut().enumerate().find_map(|(i, r)| {
+                    r[j].rem_floor_mut(&modulus);
+                    let (g, s, _) = xgcd(&r[j], &modulus);
+                    g.is_one().then_some((i, s))
+                });
+                let Some((p, inverse)) = unit else {
+                    continue;
+                };
+
+                // g_j = -u^-1 sum_{k != j} r_k g_k for the pivot relation r with r_j = u
+                let pivot = rows.swap_remove(p);
+                let factor = -inverse;
+                let expression: Vec<(usize, ZZ)> = (0..n)
+                    .filter(|&k| k != j && alive[k] && !pivot[k].is_zero())
+                    .map(|k| (k, (factor.clone() * &pivot[k]).rem_floor(&modulus)))
+                    .collect();
+
+                // Entries are reduced when they are used, so they only grow by the sums of products here
+                for r in rows.iter_mut() {
+                    let x = std::mem::replace(&mut r[j], ZZ::zero()).rem_floor(&modulus);
+                    if x.is_zero() {
+                        continue;
+                    }
+                    for (k, c) in &expression {
+                        r[*k].add_mul(&x, c);
+                    }
+                }
+                substitutions.push((j, expression));
+                alive[j] = false;
+                progress = true;
+            }
+        }
+
+        let columns: Vec<usize> = (0..n).filter(|&j| alive[j]).collect();
+        let rows = rows.into_iter().map(|r| columns.iter().map(|&j| r[j].rem_floor(&modulus)).collect()).collect();
+        (Self { modulus, substitutions, columns }, rows)
+    }
+
+    // Exponent vector x as one over the surviving columns mod m, substituting the eliminated generators
+    // in order
+    fn restrict(&self, mut x: Vec<ZZ>) -> Vec<ZZ> {
+        for (j, expression) in &self.substitutions {
+            let xj = std::mem::replace(&mut x[*j], ZZ::zero()).rem_floor(&self.modulus);
+            if xj.is_zero() {
+                continue;
+            }
+            for (k, c) in expression {
+                x[*k].add_mul(&xj, c);
+            }
+        }
+        self.columns.iter().map(|&j| x[j].rem_floor(&self.modulus)).collect()
+    }
+}
+
+// Row echelon form mod ECHELON_PRIME of the relations found independent so far, with pivots 1
+struct Echelon(Vec<Option<Vec<u64>>>);
+
+// Prime of the independence test, 2^61 - 1
+const ECHELON_PRIME: u64 = (1 << 61) - 1;
+
+fn mul_mod(x: u64, y: u64) -> u64 {
+    (x as u128 * y as u128 % ECHELON_PRIME as u128) as u64
+}
+
+impl Echelon {
+    fn new(n: usize) -> Self {
+        Self(vec![None; n])
+    }
+
+    // Adds r if it is independent of the rows so far
+    fn insert(&mut self, r: &[ZZ]) -> bool {
+        let n = r.len();
+        let prime = ZZ::from(ECHELON_PRIME);
+        let mut v: Vec<u64> = r.iter().map(|x| x.rem_floor(&prime).value.to_u64().unwrap()).collect();
+        for j in 0..n {
+            if v[j] == 0 {
+                continue;
+            }
+            match &self.0[j] {
+                // Pivots are 1
+                Some(e) => {
+                    let c = v[j];
+                    for (x, &y) in v[j..].iter_mut().zip(&e[j..]) {
+                        *x = (*x + ECHELON_PRIME - mul_mod(c, y)) % ECHELON_PRIME;
+                    }
+                }
+                None => {
+                    // Inverse by Fermat
+                    let (mut inv, mut base, mut e) = (1, v[j], ECHELON_PRIME - 2);
+                    while e > 0 {
+                        if e & 1 == 1 {
+                            inv = mul_mod(inv, base);
+                        }
+                        base = mul_mod(base, base);
+                        e >>= 1;
+                    }
+                    v[j..].iter_mut().for_each(|x| *x = mul_mod(*x, inv));
+                    self.0[j] = Some(v);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+// Primes of the determinants by CRT are below 2^DET_PRIME_BITS, so that an entry below 2^64 has room
+// for DET_LAZY_STEPS products of two reduced entries before it must be reduced again
+const DET_PRIME_BITS: u32 = 28;
+const DET_LAZY_STEPS: usize = 255;
+
+fn residue(x: &ZZ, p: u64) -> u64 {
+    match x.value.to_i64() {
+        Some(x) => x.rem_euclid(p as i64) as u64,
+        None => x.value.mod_u(p as u32) as u64,
+    }
+}
+
+// det B followed by r adj B, mod a prime p below 2^DET_PRIME_BITS, from Gaussian elimination on
+// [B^T | r^T] and back substitution for x B = r, so that r adj B = det B x. None if p divides det B.
+fn det_adjugate(basis: &[&[ZZ]], r: &[ZZ], p: u64) -> Option<Vec<u64>> {
+    let n = basis.len();
+    let mut a: Vec<Vec<u64>> = (0..n)
+        .map(|i| basis.iter().map(|b| &b[i]).chain([&r[i]]).map(|x| residue(x, p)).collect())
+        .collect();
+
+    let mut det = 1;
+    for k in 0..n {
+        if k % DET_LAZY_STEPS == 0 {
+            a[k..].iter_mut().for_each(|row| row[k..].iter_mut().for_each(|x| *x %= p));
+        }
+        let i = (k..n).find(|&i| !a[i][k].is_multiple_of(p))?;
+        a.swap(i, k);
+        if i != k {
+            det = p - det;
+        }
+        a[k][k..].iter_mut().for_each(|x| *x %= p);
+        det = det * a[k][k] % p;
+
+        // row_i - (row_i[k] / pivot) pivot, as row_i + f pivot with f reduced, in 32 by 32 bit products
+        // that vectorize
+        let inverse = inverse_mod(a[k][k] as u32, p as u32) as u64;
+        let pivot: Vec<u32> = a[k][k + 1..].iter().map(|&y| y as u32).collect();
+        for row in &mut a[k + 1..] {
+            let f = ((p - row[k] % p) * inverse % p) as u32;
+            if f == 0 {
+                continue;
+            }
+            row[k + 1..].iter_mut().zip(&pivot).for_each(|(x, &y)| *x += f as u64 * y as u64);
+        }
+    }
+
+    let mut x = vec![0; n];
+    for k in (0..n).rev() {
+        let s = (k + 1..n).fold(a[k][n], |s, j| (s + (p - a[k][j] * x[j] % p)) % p);
+        x[k] = s * inverse_mod(a[k][k] as u32, p as u32) as u64 % p;
+    }
+    Some([det].into_iter().chain(x.into_iter().map(|x| det * x % p)).collect())
+}
+
+// m with m Z^n in the lattice of all relations, from n independent relations B and one more r. Such is
+// det(B + Z r) = gcd(det B, r adj B), as the index of B in B + Z r is the denominator of
+// r B^-1 = r adj B / det B. Those come mod primes and by CRT up to the Hadamard bound, as the entries
+// of Bareiss' elimination get as large as det B. The bound is far from tight, so once a prime leaves
+// them unchanged, their gcd is taken if kills confirms that it kills the n generators.
+fn lattice_modulus(basis: &[&[ZZ]], r: &[ZZ], kills: impl Fn(&ZZ) -> bool) -> ZZ {
+    let n = basis.len();
+    // Every determinant of B with a row replaced by r is at most the product of all the norms
+    let log_norm = |x: &[ZZ]| 0.5 * x.iter().map(|x| x.value.to_f64().powi(2)).sum::<f64>().max(1.0).log2();
+    let bits = basis.iter().map(|b| log_norm(b)).sum::<f64>() + log_norm(r) + 2.0;
+    let gcd_all = |values: &[ZZ]| values.iter().fold(ZZ::zero(), |g, v| gcd(&g, v));
+
+    let mut modulus = ZZ::one();
+    let mut values = vec![ZZ::zero(); n + 1];
+    let primes = (1..1u64 << DET_PRIME_BITS).rev().step_by(2).filter(|&p| ZZ::from(p).is_probably_prime(30));
+    for p in primes {
+        if modulus.significant_bits() as f64 > bits {
+            break;
+        }
+        let Some(residues) = det_adjugate(basis, r, p) else {
+            continue;
+        };
+        // Symmetric residues, v + M t with t = (a - v) M^-1 mod p and |t| <= p / 2
+        let inverse = inverse_mod(residue(&modulus, p) as u32, p as u32) as u64;
+        let mut unchanged = true;
+        for (v, a) in values.iter_mut().zip(residues) {
+            let t = (a + p - residue(v, p)) * inverse % p;
+            if t != 0 {
+                let t = if t > p / 2 { t as i64 - p as i64 } else { t as i64 };
+                v.add_mul(&modulus, &ZZ::from(t));
+                unchanged = false;
+            }
+        }
+        modulus *= ZZ::from(p);
+        if unchanged && kills(&gcd_all(&values)) {
+            break;
+        }
+    }
+    gcd_all(&values)
+}
+
+// Hermite normal form of the lattice spanned by relations and m Z^n: upper triangular with positive
+// pivots, their product det, and entries mod m, or mod det once that is smaller. Without a modulus the
+// entries blow up exponentially.
+struct Hnf {
+    rows: Vec<Vec<ZZ>>,
+    det: ZZ,
+    modulus: ZZ,
+}
+
+impl Hnf {
+    fn new(n: usize, modulus: ZZ) -> Self {
+        let rows = (0..n)
+            .map(|i| (0..n).map(|j| if i == j { modulus.clone() } else { ZZ::zero() }).collect())
+            .collect();
+        Self { rows, det: modulus.pow(&(n as u32)), modulus }
+    }
+
+    fn insert(&mut self, r: &[ZZ]) {
+        let Self { rows, det, modulus } = self;
+        let n = rows.len();
+        let mut r = r.to_vec();
+        for j in 0..n {
+            r[j..].iter_mut().for_each(|x| x.rem_floor_mut(modulus));
+            if r[j].is_zero() {
+                continue;
+            }
+
+            // h <- s h + t r with pivot g = gcd(x, y), r <- (x / g) r - (y / g) h
+            let h = &mut rows[j];
+            let (x, y) = (h[j].clone(), r[j].clone());
+            let (g, s, t) = xgcd(&x, &y);
+            let (mut x, mut y) = (x, y);
+            x.div_exact(&g);
+            y.div_exact(&g);
+            combine(&mut h[j..], &mut r[j..], [&s, &t, &x, &y], Some(modulus));
+        }
+
+        *det = rows.iter().enumerate().map(|(j, h)| h[j].clone()).product();
+        if *det < *modulus {
+            modulus.clone_from(det);
+            for (j, h) in rows.iter_mut().enumerate() {
+                h[j + 1..].iter_mut().for_each(|x| x.rem_floor_mut(modulus));
+            }
+        }
+    }
+}
+
+// (g, s, t) with g = gcd(x, y) = s x + t y, and t = 0 if x divides y so that eliminating y
+// leaves the pivot row or column unchanged
+fn xgcd(x: &ZZ, y: &ZZ) -> (ZZ, ZZ, ZZ) {
+    if y.is_divisible(x) {
+        let s = if *x < ZZ::zero() { -ZZ::one() } else { ZZ::one() };
+        return (x.abs(), s, ZZ::zero());
+    }
+    let mut g = x.clone();
+    let mut s = y.clone();
+    let mut t = ZZ::zero();
+    g.extended_gcd_mut(&mut s, &mut t);
+    (g, s, t)
+}
+
+// (u, v) <- (s u + t v, x v - y u), optionally mod m
+fn combine_pair(u: &mut ZZ, v: &mut ZZ, [s, t, x, y]: [&ZZ; 4], m: Option<&ZZ>) {
+    let mut u1 = s.clone() * &*u;
+    u1.add_mul(t, v);
+    let mut v1 = x.clone() * &*v;
+    v1.sub_mul(y, u);
+    if let Some(m) = m {
+        u1.rem_floor_mut(m);
+        v1.rem_floor_mut(m);
+    }
+    (*u, *v) = (u1, v1);
+}
+
+// combine_pair entrywise
+fn combine(u: &mut [ZZ], v: &mut [ZZ], coefficients: [&ZZ; 4], m: Option<&ZZ>) {
+    for (u, v) in u.iter_mut().zip(v.iter_mut()) {
+        combine_pair(u, v, coefficients, m);
+    }
+}
+
+// Rows i != j of m, mutably
+fn pair_mut(m: &mut [Vec<ZZ>], i: usize, j: usize) -> (&mut Vec<ZZ>, &mut Vec<ZZ>) {
+    if i < j {
+        let (lo, hi) = m.split_at_mut(j);
+        (&mut lo[i], &mut hi[0])
+    } else {
+        let (lo, hi) = m.split_at_mut(i);
+        (&mut hi[0], &mut lo[j])
+    }
+}
+
+// Smith normal form of the relation matrix a mod its determinant N, as the invariants gcd(d_i, N)
+// and the column transformation V with its inverse W, both mod N
+fn smith(mut a: Vec<Vec<ZZ>>, det: &ZZ) -> (Vec<ZZ>, Vec<Vec<ZZ>>, Vec<Vec<ZZ>>) {
+    let n = a.len();
+    let identity = |n: usize| -> Vec<Vec<ZZ>> {
+        (0..n).map(|i| (0..n).map(|j| if i == j { ZZ::one() } else { ZZ::zero() }).collect()).collect()
+    };
+    let mut v = identity(n);
+    let mut w = identity(n);
+    let mut diag = Vec::with_capacity(n);
+
+    for k in 0..n {
+        // Pivot, preferably a unit
+        let entries = || (k..n).flat_map(|i| (k..n).map(move |j| (i, j)));
+        let pivot = entries()
+            .find(|&(i, j)| a[i][j].is_one())
+            .or_else(|| entries().find(|&(i, j)| !a[i][j].is_zero()));
+        let Some((pi, pj)) = pivot else {
+            diag.extend((k..n).map(|_| det.clone()));
+            break;
+        };
+        a.swap(k, pi);
+        if pj != k {
+            a.iter_mut().for_each(|row| row.swap(k, pj));
+            v.iter_mut().for_each(|row| row.swap(k, pj));
+            w.swap(k, pj);
+        }
+
+        loop {
+            // Clear column k below the pivot with row operations
+            for i in k + 1..n {
+                if a[i][k].is_zero() {
+                    continue;
+                }
+                let (x, y) = (a[k][k].clone(), a[i][k].clone());
+                let (g, s, t) = xgcd(&x, &y);
+                let (mut x, mut y) = (x, y);
+                x.div_exact(&g);
+                y.div_exact(&g);
+                let (rk, ri) = pair_mut(&mut a, k, i);
+                combine(&mut rk[k..], &mut ri[k..], [&s, &t, &x, &y], Some(det));
+            }
+
+            // Clear row k right of the pivot with column operations, tracked in V and W
+            for j in k + 1..n {
+                if a[k][j].is_zero() {
+                    continue;
+                }
+                let (x, y) = (a[k][k].clone(), a[k][j].clone());
+                let (g, s, t) = xgcd(&x, &y);
+                let (mut x, mut y) = (x, y);
+                x.div_exact(&g);
+                y.div_exact(&g);
+
+                // Columns (k, j) <- (k, j) [[s, -y], [t, x]], rows (k, j) of W <- [[x, y], [-t, s]] (k, j)
+                for row in a.iter_mut().chain(v.iter_mut()) {
+                    let (lo, hi) = row.split_at_mut(j);
+                    combine_pair(&mut lo[k], &mut hi[0], [&s, &t, &x, &y], Some(det));
+                }
+                let (wk, wj) = pair_mut(&mut w, k, j);
+                combine(wk, wj, [&x, &y, &s, &t], Some(det));
+            }
+
+            if (k + 1..n).any(|i| !a[i][k].is_zero()) {
+                continue;
+            }
+
+            // The invariant must divide all remaining entries, otherwise bring one into row k
+            let d = gcd(&a[k][k], det);
+            let bad = (k + 1..n).find(|&i| (k + 1..n).any(|j| !a[i][j].is_divisible(&d)));
+            match bad {
+                Some(i) => {
+                    let (rk, ri) = pair_mut(&mut a, k, i);
+                    for (x, y) in rk[k..].iter_mut().zip(&ri[k..]) {
+                        *x += y.clone();
+                        x.rem_floor_mut(det);
+                    }
+                }
+                None => break,
+            }
+        }
+        diag.push(gcd(&a[k][k], det));
+    }
+    (diag, v, w)
+}
+
+impl<T: ClassConfig<Int = ZZ>> ClassGroupStructure<T> {
+    // Structure of Cl(D) with the default factor base bound, see compute_with_bound
+    pub fn compute(verify: bool) -> Result<Self, StructureError> {
+        let ln = T::discriminant().value.to_f64().abs().ln();
+        let bound = (FACTOR_BASE_EXPONENT * (ln * ln.ln()).sqrt()).exp();
+        Self::compute_with_bound(bound.max(20.0) as u64, verify)
+    }
+
+    // Structure of Cl(D) from a factor base of the primes up to bound. With verify, every prime up to
+    // Bach's bound 6 ln^2 |D| is then checked to lie in the group generated by the factor base, which
+    // under GRH proves that the factor base generates Cl(D). Fails if that check fails, or if the
+    // relation search gives up before det L comes down to the bound.
+    pub fn compute_with_bound(bound: u64, verify: bool) -> Result<Self, StructureError> {
+        if bound >= 1 << 31 {
+            return Err(StructureError::BoundTooLarge);
+        }
+        let disc = T::discriminant();
+        let factor_base: Vec<FactorBasePrime<T>> = (2..=bound as u32).filter_map(FactorBasePrime::new).collect();
+        let n = factor_base.len();
+        if n == 0 {
+            return Err(StructureError::EmptyFactorBase);
+        }
+        let sieve = Sieve::new(factor_base);
+
+        // Lower bound of h(D), exact for small |D| and heuristic above, see the struct doc
+        let lower = if disc > ZZ::from(-COUNT_FORMS_BOUND) {
+            ZZ::from(count_reduced_forms(i64::from(disc.clone())))
+        } else {
+            let (estimate, radius) = estimate(&disc);
+            (estimate - &radius).max(ZZ::one())
+        };
+        let twice_lower = lower * ZZ::from(2u64);
+
+        // Elimination on the first n + RELATION_EXCESS relations
+        let mut search = RelationSearch::new(&sieve);
+        let limit = RELATION_LIMIT_FACTOR * (n + RELATION_BATCH);
+        let relations = (0..n + RELATION_EXCESS).map(|_| search.relation()).collect::<Result<Vec<_>, _>>()?;
+        let (elimination, rest) = Elimination::new(n, &relations);
+        let mut count = relations.len();
+
+        // d independent relations over the surviving columns and one more give the modulus, in rounds
+        // of RELATION_BATCH more relations until they are there
+        let d = elimination.columns.len();
+        let mut rows: Vec<Vec<ZZ>> = rest.iter().map(|r| elimination.restrict(dense(n, r))).collect();
+        let mut echelon = Echelon::new(d);
+        let (mut basis, mut extra) = (Vec::with_capacity(d), None);
+        let mut next = 0;
+        while basis.len() < d || extra.is_none() {
+            if next == rows.len() {
+                // Columns without a pivot may be primes no relation reached so far, which would take many
+                // rounds to turn up otherwise
+                let missing = (0..d).filter(|&j| echelon.0[j].is_none()).map(|j| elimination.columns[j]);
+                search.wanted = missing.filter(|j| sieve.pool.binary_search(j).is_ok()).collect();
+                count += RELATION_BATCH;
+                if count > limit {
+                    return Err(StructureError::RelationsExhausted);
+                }
+                for _ in 0..RELATION_BATCH {
+                    rows.push(elimination.restrict(dense(n, &search.relation()?)));
+                }
+            }
+            if echelon.insert(&rows[next]) {
+                basis.push(next);
+            } else {
+                extra = Some(next);
+            }
+            next += 1;
+        }
+        let generators: Vec<&ClassGroup<T>> = elimination.columns.iter().map(|&j| &sieve.factor_base[j].form).collect();
+        let kills = |m: &ZZ| generators.iter().all(|&g| (g * m).is_zero());
+        let basis: Vec<&[ZZ]> = basis.iter().map(|&i| &rows[i][..]).collect();
+        let modulus = lattice_modulus(&basis, &rows[extra.unwrap()], kills);
+
+        // Then rounds of RELATION_BATCH more relations until det L < 2 lower, and once more with det L
+        // unchanged
+        let (reduction, rows) = ModularElimination::new(d, rows, modulus.clone());
+        let mut lattice = Hnf::new(reduction.columns.len(), modulus);
+        rows.iter().for_each(|r| lattice.insert(r));
+        let mut accepted: Option<ZZ> = None;
+        loop {
+            if lattice.det < twice_lower {
+                if accepted.as_ref() == Some(&lattice.det) {
+                    break;
+                }
+                accepted = Some(lattice.det.clone());
+            } else {
+                accepted = None;
+            }
+
+            count += RELATION_BATCH;
+            if count > limit {
+                return Err(StructureError::RelationsExhausted);
+            }
+            for _ in 0..RELATION_BATCH {
+                let r = search.relation()?;
+                lattice.insert(&reduction.restrict(elimination.restrict(dense(n, &r))));
+            }
+        }
+
+        // The large primes of partial relations are already known to be generated by the factor base
+        if verify {
+            let rng = &mut rng::<T>(b"verify", &[]);
+            let bach = 6.0 * disc.value.to_f64().abs().ln().powi(2);
+            let mut generated: HashSet<u32> = search.partials.keys().copied().collect();
+            for p in bound as u32 + 1..=bach as u32 {
+                if generated.contains(&p) {
+                    continue;
+                }
+                if let Some(q) = FactorBasePrime::<T>::new(p) {
+                    if !sieve.is_generated(&q, &generated, rng) {
+                        return Err(StructureError::NotGenerated(p));
+                    }
+                    generated.insert(p);
+                }
+            }
+        }
+
+        let Hnf { rows, det, .. } = lattice;
+        let (diag, v, w) = smith(rows, &det);
+
+        let forms: Vec<ClassGroup<T>> = reduction
+            .columns
+            .iter()
+            .map(|&k| sieve.factor_base[elimination.columns[k]].form.clone())
+            .collect();
+        let nontrivial: Vec<usize> = (0..forms.len()).filter(|&k| !diag[k].is_one()).collect();
+        Ok(Self {
+            invariants: nontrivial.iter().map(|&k| diag[k].clone()).collect(),
+            generators: nontrivial.iter().map(|&k| ClassGroup::msm(&forms, &w[k])).collect(),
+            sieve,
+            elimination,
+            reduction,
+            coordinate_map: v
+                .iter()
+                .map(|row| nontrivial.iter().map(|&k| row[k].rem_floor(&diag[k])).collect())
+                .collect(),
+        })
+    }
+
+    // h(D), the product of the invariants
+    pub fn class_number(&self) -> ZZ {
+        self.invariants.iter().product()
+    }
+
+    // Coordinates c with f = sum c_i generators[i] and 0 <= c_i < invariants[i]. Fails if the random
+    // walk from f finds no decomposition over the factor base, which is unlikely once verify passed.
+    pub fn coordinates(&self, f: &ClassGroup<T>) -> Result<Vec<ZZ>, StructureError> {
+        let x = self.sieve.exponents(f).ok_or(StructureError::NoDecomposition)?;
+
+        let x = self.reduction.restrict(self.elimination.restrict(x.into_iter().map(ZZ::from).collect()));
+        Ok((0..self.invariants.len())
+            .map(|i| {
+                let mut c = ZZ::zero();
+                for (xj, row) in x.iter().zip(&self.coordinate_map) {
+                    c.add_mul(xj, &row[i]);
+                }
+                c.rem_floor(&self.invariants[i])
+            })
+            .collect())
+    }
+
+    // sum c_i generators[i]
+    pub fn element(&self, coordinates: &[ZZ]) -> ClassGroup<T> {
+        assert_eq!(coordinates.len(), self.generators.len(), "one coordinate per generator");
+        ClassGroup::msm(&self.generators, coordinates)
+    }
+}
+
+// Deterministic rng for the random choices of the discriminant of T
+fn rng<T: ClassConfig<Int = ZZ>>(label: &[u8], data: &[u8]) -> Shake256 {
+    let disc = discriminant_bytes::<T>();
+    let mut rng = Shake256::new();
+    for x in [&b"ark-guo class group structure"[..], label, &disc, data] {
+        rng.update(&(x.len() as u64).to_le_bytes());
+        rng.update(x);
+    }
+    rng
+}
+
+// x^-1 mod p for a prime p < 2^32 not dividing x
+fn inverse_mod(x: u32, p: u32) -> u32 {
+    let p = p as u64;
+    let (mut r, mut base, mut e) = (1u64, x as u64 % p, p - 2);
+    while e > 0 {
+        if e & 1 == 1 {
+            r = r * base % p;
+        }
+        base = base * base % p;
+        e >>= 1;
+    }
+    r as u32
+}
+
+// Self-initializing quadratic sieve over the polynomials phi(x) = a x^2 + b x + c of forms (a, b, c)
+// whose a = q_1 ... q_k is a product of factor base primes, so that (a, b, c) is the product of the
+// prime ideals over the q_i with the signs of b mod 2 q_i. The substitution (x X - Y, X) takes
+// (a, b, c) to the equivalent form (phi(x), -(2ax + b), a), so if phi(x) is smooth, the exponents of
+// (a, b, c) minus those of (phi(x), -(2ax + b), a) are a relation. With a about sqrt(|D| / 2) / M,
+// |phi(x)| stays within a small factor of sqrt|D| M over [-M, M], and all 2^(k-1) choices of the
+// signs but the overall one give different polynomials for the same a.
+#[derive(Clone, Debug)]
+struct Sieve<T: ClassConfig<Int = ZZ>> {
+    factor_base: Vec<FactorBasePrime<T>>,
+    disc: ZZ,
+    half_width: i64,
+    // ln of the best a, sqrt(|D| / 2) / M
+    target: f64,
+    // Odd split primes for the a of polynomials, as indices into the factor base
+    pool: Vec<usize>,
+    // Rounded log2 of the factor base primes
+    logs: Vec<u8>,
+    // Product of the factor base primes, for the smoothness test of the random walk
+    product: ZZ,
+    // Bound of the large primes of partial relations
+    large_bound: u64,
+}
+
+// Polynomial of the form (a, b, c) with a a product of factor base primes, or of those and one beyond,
+// and the exponents of the form over the factor base
+struct Polynomial {
+    a: ZZ,
+    b: ZZ,
+    c: ZZ,
+    exponents: Relation,
+    // (2a)^-1 mod p for the sieved factor base primes, 0 for the others, shared by the polynomials of a
+    inverses: Rc<[u32]>,
+}
+
+impl<T: ClassConfig<Int = ZZ>> Sieve<T> {
+    fn new(factor_base: Vec<FactorBasePrime<T>>) -> Self {
+        let disc = T::discriminant();
+        let abs = disc.value.to_f64().abs();
+        let half_width = (abs.powf(0.25) as i64).clamp(SIEVE_MIN_HALF_WIDTH, SIEVE_MAX_HALF_WIDTH);
+        let target = (abs / 2.0).sqrt().ln() - (half_width as f64).ln();
+        let pool = (0..factor_base.len()).filter(|&j| factor_base[j].p > 2 && !factor_base[j].ramified).collect();
+        let logs = factor_base.iter().map(|q| (q.p as f64).log2().round() as u8).collect();
+        let product = factor_base.iter().map(|q| ZZ::from(q.p)).product();
+        let large_bound = (LARGE_PRIME_FACTOR * factor_base[factor_base.len() - 1].p as u64).min(u32::MAX as u64);
+        Self { factor_base, disc, half_width, target, pool, logs, product, large_bound }
+    }
+
+    // Primes of the pool other than exclude with product about e^target, at least one unless there are
+    // none. As few as the factor base allows, all but the last picked at random near the same size, and
+    // the last one among the LAST_PRIME_CHOICES closest to the gap to e^target.
+    fn choose_primes(&self, target: f64, exclude: Option<u32>, rng: &mut Shake256) -> Vec<usize> {
+        let pool: Vec<usize> = self.pool.iter().copied().filter(|&j| Some(self.factor_base[j].p) != exclude).collect();
+        if pool.is_empty() {
+            return Vec::new();
+        }
+        let ln = |i: usize| (self.factor_base[pool[i]].p as f64).ln();
+        let k = ((target / ln(pool.len() - 1)).ceil() as usize).clamp(1, pool.len());
+
+        // Pool positions within POLYNOMIAL_WINDOW of target / k, or the 4 k closest if there are fewer
+        let size = target / k as f64;
+        let mut near: Vec<usize> = (0..pool.len()).collect();
+        near.sort_by(|&i, &j| (ln(i) - size).abs().total_cmp(&(ln(j) - size).abs()));
+        let window = near.iter().take_while(|&&i| (ln(i) - size).abs() <= POLYNOMIAL_WINDOW.ln()).count();
+        near.truncate(window.max(4 * k));
+
+        let mut chosen: Vec<usize> = Vec::with_capacity(k);
+        while chosen.len() + 1 < k {
+            let i = near[rng.gen_range(0..near.len())];
+            if !chosen.contains(&i) {
+                chosen.push(i);
+            }
+        }
+        let rest = target - chosen.iter().map(|&i| ln(i)).sum::<f64>();
+        let mut last: Vec<usize> = (0..pool.len()).filter(|i| !chosen.contains(i)).collect();
+        last.sort_by(|&i, &j| (ln(i) - rest).abs().total_cmp(&(ln(j) - rest).abs()));
+        let last = last[rng.gen_range(0..last.len().min(LAST_PRIME_CHOICES))];
+        chosen.push(last);
+
+        let mut primes: Vec<usize> = chosen.into_iter().map(|i| pool[i]).collect();
+        primes.sort_unstable();
+        primes
+    }
+
+    // The polynomials for a product of k random pool primes, times extra if given. Each b is the CRT of
+    // +-b_i mod q_i, with the sign of extra fixed to +, or else that of the first prime, so there are
+    // 2^k of them with extra and 2^(k-1) without.
+    fn polynomials(&self, extra: Option<&FactorBasePrime<T>>, rng: &mut Shake256) -> Vec<Polynomial> {
+        let target = self.target - extra.map_or(0.0, |q| (q.p as f64).ln());
+        let chosen = self.choose_primes(target, extra.map(|q| q.p), rng);
+        let moduli: Vec<(u32, u32)> = extra
+            .into_iter()
+            .chain(chosen.iter().map(|&j| &self.factor_base[j]))
+            .map(|q| (q.p, q.b % q.p))
+            .collect();
+
+        // Factor base indices of the primes of a
+        let mut primes = chosen.clone();
+        if let Some(j) = extra.and_then(|q| self.factor_base.binary_search_by_key(&q.p, |f| f.p).ok()) {
+            primes.push(j);
+            primes.sort_unstable();
+        }
+
+        // a and the CRT basis e_i = 1 mod q_i, 0 mod q_j for j != i, times b_i
+        let a: ZZ = moduli.iter().map(|&(p, _)| ZZ::from(p)).product();
+        let basis: Vec<ZZ> = moduli
+            .iter()
+            .map(|&(p, r)| {
+                let mut cofactor = a.clone();
+                cofactor.value.div_exact_u_mut(p);
+                let inverse = inverse_mod(cofactor.value.mod_u(p), p);
+                cofactor * &ZZ::from(inverse as u64 * r as u64 % p as u64)
+            })
+            .collect();
+
+        // The odd split primes from SIEVE_MIN_PRIME on that do not divide a are sieved
+        let inverses: Rc<[u32]> = self
+            .factor_base
+            .iter()
+            .enumerate()
+            .map(|(j, q)| {
+                if q.p < SIEVE_MIN_PRIME || q.ramified || primes.binary_search(&j).is_ok() {
+                    return 0;
+                }
+                inverse_mod((a.value.mod_u(q.p) as u64 * 2 % q.p as u64) as u32, q.p)
+            })
+            .collect();
+
+        let free = moduli.len().saturating_sub(1);
+        let fixed = basis.len() - free;
+        (0..1usize << free)
+            .map(|signs| {
+                let mut b: ZZ = basis[..fixed].iter().sum();
+                for (i, e) in basis[fixed..].iter().enumerate() {
+                    if signs >> i & 1 == 1 {
+                        b -= e;
+                    } else {
+                        b += e;
+                    }
+                }
+                // b = D mod 2 makes b^2 = D mod 4a, with a odd
+                b.rem_floor_mut(&a);
+                if b.is_odd() != self.disc.is_odd() {
+                    b += &a;
+                }
+                let mut c = b.clone() * &b - &self.disc;
+                c.div_exact(&(ZZ::from(4u64) * &a));
+
+                let exponents = primes.iter().map(|&j| (j, self.factor_base[j].exponent(1, &b))).collect();
+                Polynomial { a: a.clone(), b, c, exponents, inverses: inverses.clone() }
+            })
+            .collect()
+    }
+
+    // Exponents over the factor base of (phi(x), -(2ax + b), a) for the x in [-M, M] with phi(x) smooth,
+    // or smooth but for a prime below large
+    fn smooth_values(&self, poly: &Polynomial, large: u64) -> Vec<Partial> {
+        let Polynomial { a, b, inverses, .. } = poly;
+        let m = self.half_width;
+        let len = 2 * m as usize + 1;
+
+        // Positions i = x + M of the two roots of phi mod p for the sieved primes,
+        // x = (-b +- r) / 2a mod p with r = b_q the root of D mod p
+        let roots: Vec<Option<(usize, usize)>> = self
+            .factor_base
+            .iter()
+            .zip(inverses.iter())
+            .map(|(q, &inverse)| {
+                if inverse == 0 {
+                    return None;
+                }
+                let (p, inverse) = (q.p as u64, inverse as u64);
+                let (r, minus_b) = ((q.b % q.p) as u64, p - b.value.mod_u(q.p) as u64);
+                let position = |x: u64| ((x % p * inverse % p) + m as u64 % p) as usize % q.p as usize;
+                Some((position(minus_b + r), position(minus_b + p - r)))
+            })
+            .collect();
+
+        let mut sieve = vec![0u8; len];
+        for ((q, root), &log) in self.factor_base.iter().zip(&roots).zip(&self.logs) {
+            let Some((r1, r2)) = *root else {
+                continue;
+            };
+            let p = q.p as usize;
+            (r1..len).step_by(p).for_each(|i| sieve[i] += log);
+            if r2 != r1 {
+                (r2..len).step_by(p).for_each(|i| sieve[i] += log);
+            }
+        }
+
+        // log2 phi(x) = log2(((2ax + b)^2 - D) / 4a), smallest at x = -b / 2a
+        let (af, bf, df) = (a.value.to_f64(), b.value.to_f64(), self.disc.value.to_f64());
+        let log_phi = |x: f64| (((2.0 * af * x + bf).powi(2) - df) / (4.0 * af)).log2();
+        let vertex = -bf / (2.0 * af);
+        let slack = SIEVE_SLACK_BITS + (large as f64).log2();
+
+        let mut found = Vec::new();
+        for (block, logs) in sieve.chunks(SIEVE_BLOCK).enumerate() {
+            let start = block * SIEVE_BLOCK;
+            let end = start + logs.len();
+            let x = vertex.clamp((start as i64 - m) as f64, (end as i64 - 1 - m) as f64);
+            let threshold = (log_phi(x) - slack).max(0.0) as u8;
+            for (i, _) in (start..).zip(logs).filter(|&(_, &log)| log >= threshold) {
+                let x = ZZ::from(i as i64 - m);
+                let hits = |j: usize| roots[j].map(|(r1, r2)| (i % self.factor_base[j].p as usize, r1, r2));
+                if let Some(r) = self.decompose(poly, &x, hits, large) {
+                    found.push(r);
+                }
+            }
+        }
+        found
+    }
+
+    // Exponents of (phi(x), -(2ax + b), a) if phi(x) is smooth, or smooth but for a prime P < large that
+    // does not divide D. hits(j) gives i mod p and the root positions of the sieved primes, the others
+    // are tested by division
+    fn decompose(
+        &self,
+        poly: &Polynomial,
+        x: &ZZ,
+        hits: impl Fn(usize) -> Option<(usize, usize, usize)>,
+        large: u64,
+    ) -> Option<Partial> {
+        let Polynomial { a, b, c, .. } = poly;
+        let mut value = a.clone() * x;
+        value += b;
+        value *= x.clone();
+        value += c;
+        let mut middle = ZZ::from(2u64) * a * x;
+        middle += b;
+        let middle = -middle;
+
+        let mut r = Vec::new();
+        for (j, q) in self.factor_base.iter().enumerate() {
+            let hit = match hits(j) {
+                Some((i, r1, r2)) => i == r1 || i == r2,
+                None => value.value.is_divisible_u(q.p),
+            };
+            if !hit {
+                continue;
+            }
+            let mut k = 0;
+            while value.value.is_divisible_u(q.p) {
+                value.value.div_exact_u_mut(q.p);
+                k += 1;
+            }
+            if k == 0 || (q.ramified && k > 1) {
+                return None;
+            }
+            r.push((j, q.exponent(k, &middle)));
+            if value.is_one() {
+                break;
+            }
+        }
+
+        if value.is_one() {
+            return Some((r, None));
+        }
+        let p = value.value.to_u32().filter(|&p| (p as u64) < large)?;
+        if !value.is_probably_prime(30) || self.disc.value.is_divisible_u(p) {
+            return None;
+        }
+        Some((r, Some((p, middle.value.mod_u(p)))))
+    }
+
+    // Relations from the values of a polynomial that are smooth or smooth but for a large prime, the
+    // exponents of its form minus those of the forms of the values. The partial ones are relations of
+    // the prime ideal over the large prime.
+    fn relations(&self, poly: &Polynomial) -> Vec<Partial> {
+        self.smooth_values(poly, self.large_bound)
+            .into_iter()
+            .filter_map(|(d, large)| Some((add_multiple(&poly.exponents, -1, &d)?, large)))
+            .filter(|(r, large)| !r.is_empty() || large.is_some())
+            .collect()
+    }
+
+    // Exponents over the factor base of the prime ideal q of a prime beyond the factor base, from a
+    // smooth value of a polynomial whose a is a multiple of q. Tiny factor bases give too few such
+    // polynomials, so then from a random walk as in exponents.
+    fn prime_exponents(&self, q: &FactorBasePrime<T>, rng: &mut Shake256) -> Option<Vec<i64>> {
+        let n = self.factor_base.len();
+        let mut tried = 0;
+        while self.pool.len() >= SIEVE_MIN_POOL && tried < PRIME_POLYNOMIALS {
+            for poly in self.polynomials(Some(q), rng) {
+                tried += 1;
+                if let Some((d, _)) = self.smooth_values(&poly, 1).first() {
+                    // q + exponents of poly = d
+                    let mut x = vec![0i64; n];
+                    d.iter().for_each(|&(j, e)| x[j] += e);
+                    poly.exponents.iter().for_each(|&(j, e)| x[j] -= e);
+                    return Some(x);
+                }
+            }
+        }
+        self.walk(&q.form, false, rng)
+    }
+
+    // Whether the prime ideal q of a prime beyond the factor base lies in the group generated by it,
+    // given the primes beyond it already known to, from a value of a polynomial whose a is a multiple
+    // of q that is smooth but for at most one of those
+    fn is_generated(&self, q: &FactorBasePrime<T>, generated: &HashSet<u32>, rng: &mut Shake256) -> bool {
+        let mut tried = 0;
+        while self.pool.len() >= SIEVE_MIN_POOL && tried < PRIME_POLYNOMIALS {
+            for poly in self.polynomials(Some(q), rng) {
+                tried += 1;
+                let values = self.smooth_values(&poly, q.p as u64);
+                if values.iter().any(|(_, large)| large.is_none_or(|(p, _)| generated.contains(&p))) {
+                    return true;
+                }
+            }
+        }
+        self.walk(&q.form, false, rng).is_some()
+    }
+
+    // Exponents x with f = sum x_j g_j over the factor base
+    fn exponents(&self, f: &ClassGroup<T>) -> Option<Vec<i64>> {
+        let mut bytes = Vec::new();
+        f.serialize_compressed(&mut bytes).unwrap();
+        self.walk(f, true, &mut rng::<T>(b"exponents", &bytes))
+    }
+
+    // Random walk from f by factor base forms until the reduced a is smooth, or with large_prime
+    // smooth but for one prime beyond the factor base, whose prime ideal then goes through
+    // prime_exponents
+    fn walk(&self, f: &ClassGroup<T>, large_prime: bool, rng: &mut Shake256) -> Option<Vec<i64>> {
+        let n = self.factor_base.len();
+        let largest = self.factor_base[n - 1].p as u64;
+        let large_bound = ZZ::from((largest * largest).min(u32::MAX as u64));
+        let mut walk = f.clone();
+        let mut steps = vec![0i64; n];
+        let mut t = ClassGroup::default();
+        for _ in 0..DECOMPOSE_STEPS {
+            // The smooth part of a is gcd(a, product^(2^e) mod a) for 2^e at least the bits of a
+            let a = &walk.a;
+            let e = ZZ::from(2u64).pow(&(32 - a.significant_bits().leading_zeros()));
+            let smooth = gcd(a, &self.product.pow_mod(&e, a));
+            let mut large = a.clone();
+            large.div_exact(&smooth);
+
+            let large_exponents = if large.is_one() {
+                Some(vec![0; n])
+            } else if large_prime && large < large_bound && large.is_probably_prime(30) {
+                FactorBasePrime::<T>::new(large.value.to_u32().unwrap()).and_then(|q| {
+                    let mut x = self.prime_exponents(&q, rng)?;
+                    let sign = q.exponent(1, &walk.b);
+                    x.iter_mut().for_each(|x| *x *= sign);
+                    Some(x)
+                })
+            } else {
+                None
+            };
+
+            if let Some(mut x) = large_exponents {
+                // The form (smooth, b) as the value at x = 0 of a polynomial with middle coefficient b
+                let poly = Polynomial {
+                    a: ZZ::zero(),
+                    b: -walk.b.clone(),
+                    c: smooth,
+                    exponents: Vec::new(),
+                    inverses: Rc::new([]),
+                };
+                let (s, _) = self.decompose(&poly, &ZZ::zero(), |_| None, 1)?;
+                s.iter().for_each(|&(j, e)| x[j] += e);
+                return Some(x.iter().zip(&steps).map(|(x, e)| x - e).collect());
+            }
+
+            let j = rng.gen_range(0..n);
+            ClassGroup::nucomp(&mut t, &walk, &self.factor_base[j].form);
+            std::mem::swap(&mut walk, &mut t);
+            steps[j] += 1;
+        }
+        None
+    }
+}
+
+// Distinct relations from the sieve, one polynomial at a time. The a of the polynomials runs through
+// multiples of each pool prime in turn, so that every column gets relations with a +-1 for the
+// elimination, which the large primes would rarely get from smooth values. Partial relations are kept
+// by large prime, each further one with the same large prime gives a relation.
+struct RelationSearch<'a, T: ClassConfig<Int = ZZ>> {
+    sieve: &'a Sieve<T>,
+    rng: Shake256,
+    // Pool position of the next prime in a
+    next: usize,
+    // Factor base positions of pool primes to put into a before the next ones
+    wanted: Vec<usize>,
+    polynomials: Vec<Polynomial>,
+    found: Vec<Relation>,
+    seen: HashSet<Relation>,
+    // First partial relation of each large prime, with the b mod P of its prime ideal
+    partials: HashMap<u32, (Relation, u32)>,
+}
+
+impl<'a, T: ClassConfig<Int = ZZ>> RelationSearch<'a, T> {
+    fn new(sieve: &'a Sieve<T>) -> Self {
+        Self {
+            sieve,
+            rng: rng::<T>(b"relations", &[]),
+            next: 0,
+            wanted: Vec::new(),
+            polynomials: Vec::new(),
+            found: Vec::new(),
+            seen: HashSet::new(),
+            partials: HashMap::new(),
+        }
+    }
+
+    fn relation(&mut self) -> Result<Relation, StructureError> {
+        let mut barren = 0;
+        loop {
+            while let Some(r) = self.found.pop() {
+                if self.seen.insert(r.clone()) {
+                    return Ok(r);
+                }
+            }
+            if barren == POLYNOMIAL_LIMIT {
+                return Err(StructureError::RelationsExhausted);
+            }
+            barren += 1;
+
+            if self.sieve.pool.len() < SIEVE_MIN_POOL {
+                let r = self.walk_relation()?;
+                self.found.push(r);
+                continue;
+            }
+            if self.polynomials.is_empty() {
+                let j = self.wanted.pop().unwrap_or_else(|| {
+                    let j = self.sieve.pool[self.next];
+                    self.next = (self.next + 1) % self.sieve.pool.len();
+                    j
+                });
+                self.polynomials = self.sieve.polynomials(Some(&self.sieve.factor_base[j]), &mut self.rng);
+            }
+            let poly = self.polynomials.pop().unwrap();
+            for (r, large) in self.sieve.relations(&poly) {
+                let Some((p, b)) = large else {
+                    self.found.push(r);
+                    continue;
+                };
+                // r and the first partial are the same prime ideal if b agrees, otherwise inverses
+                match self.partials.entry(p) {
+                    Entry::Vacant(e) => {
+                        e.insert((r, b));
+                    }
+                    Entry::Occupied(e) => {
+                        let (first, c) = e.get();
+                        let sign = if *c == b { -1 } else { 1 };
+                        if let Some(r) = add_multiple(&r, sign, first).filter(|r| !r.is_empty()) {
+                            self.found.push(r);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Random product of factor base forms minus the exponents the walk from it finds
+    fn walk_relation(&mut self) -> Result<Relation, StructureError> {
+        let n = self.sieve.factor_base.len();
+        let forms: Vec<ClassGroup<T>> = self.sieve.factor_base.iter().map(|q| q.form.clone()).collect();
+        loop {
+            let mut e = vec![0i64; n];
+            for _ in 0..RELATION_FORMS {
+                e[self.rng.gen_range(0..n)] += self.rng.gen_range(-RELATION_EXPONENT..=RELATION_EXPONENT);
+            }
+            let f = ClassGroup::msm(&forms, &e.iter().map(|&e| ZZ::from(e)).collect::<Vec<_>>());
+            let x = self.sieve.walk(&f, false, &mut self.rng).ok_or(StructureError::RelationsExhausted)?;
+            let r: Relation = x.iter().zip(&e).enumerate().filter(|(_, (x, e))| x != e).map(|(j, (x, e))| (j, x - e)).collect();
+            if !r.is_empty() {
+                return Ok(r);
+            }
+        }
+    }
+}
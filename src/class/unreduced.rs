@@ -0,0 +1,108 @@
+use ark_std::{
+    hash::{Hash, Hasher},
+    ops::{Add, AddAssign},
+    Zero,
+};
+
+use crate::{
+    class::{config::ClassConfig, ClassGroup},
+    Integer,
+};
+
+// Form of discriminant T::discriminant() that is only partially reduced
+// Compositions run NUCOMP/NUDUPL without the final reduction, and a full reduction only happens
+// on demand through reduce() or once a or b grows past T::unreduced_bound_bits(). c is left out, as it
+// is near |D| / 4a for small a even in reduced forms.
+// Conversions from and to ClassGroup<T> are explicit, through From and reduce().
+// Equality and hashing are those of the classes, two forms compare equal if they reduce to the same form.
+#[derive(Clone, Debug, Default)]
+pub struct UnreducedForm<T: ClassConfig> {
+    form: ClassGroup<T>,
+}
+
+impl<T: ClassConfig> UnreducedForm<T> {
+    // Coefficients (a, b, c), not necessarily reduced
+    pub fn coefficients(&self) -> (&T::Int, &T::Int, &T::Int) {
+        (&self.form.a, &self.form.b, &self.form.c)
+    }
+
+    // Fully reduced representative
+    pub fn reduce(mut self) -> ClassGroup<T> {
+        self.form.reduce();
+        self.form
+    }
+
+    // Whether a or b exceeds the bound and the form should be reduced before the next composition
+    pub fn exceeds_bound(&self) -> bool {
+        Self::bound_exceeded(&self.form)
+    }
+
+    // Square without the final reduction
+    #[must_use]
+    pub fn double(&self) -> Self {
+        let mut r = ClassGroup::default();
+        ClassGroup::nudupl_unreduced(&mut r, &self.form);
+        Self::from_partial(r)
+    }
+
+    // Product of all forms in iter, reducing only when the coefficients outgrow the bound
+    pub fn product<I: IntoIterator<Item = ClassGroup<T>>>(iter: I) -> ClassGroup<T> {
+        iter.into_iter()
+            .fold(Self::from(ClassGroup::zero()), |acc, f| acc + &Self::from(f))
+            .reduce()
+    }
+
+    fn from_partial(mut form: ClassGroup<T>) -> Self {
+        if Self::bound_exceeded(&form) {
+            form.reduce();
+        }
+        Self { form }
+    }
+
+    fn bound_exceeded(form: &ClassGroup<T>) -> bool {
+        let bound = T::unreduced_bound_bits();
+        form.a.significant_bits() > bound || form.b.significant_bits() > bound
+    }
+}
+
+impl<T: ClassConfig> From<ClassGroup<T>> for UnreducedForm<T> {
+    fn from(form: ClassGroup<T>) -> Self {
+        Self { form }
+    }
+}
+
+// Compares the reduced representatives, as the unreduced ones differ within a class. Each call clones
+// and fully reduces both forms, so reduce once and compare the ClassGroups to compare repeatedly.
+impl<T: ClassConfig> PartialEq for UnreducedForm<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.clone().reduce() == other.clone().reduce()
+    }
+}
+
+impl<T: ClassConfig> Eq for UnreducedForm<T> {}
+
+// Hashes the reduced representative, consistent with PartialEq, at the cost of a clone and a full
+// reduction per call
+impl<T: ClassConfig> Hash for UnreducedForm<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.clone().reduce().hash(state);
+    }
+}
+
+impl<'a, T: ClassConfig> Add<&'a Self> for UnreducedForm<T> {
+    type Output = Self;
+
+    fn add(self, rhs: &'a Self) -> Self::Output {
+        let mut r = ClassGroup::default();
+        ClassGroup::nucomp_unreduced(&mut r, &self.form, &rhs.form);
+        Self::from_partial(r)
+    }
+}
+
+impl<'a, T: ClassConfig> AddAssign<&'a Self> for UnreducedForm<T> {
+    fn add_assign(&mut self, rhs: &'a Self) {
+        let mut r = ClassGroup::default();
+        ClassGroup::nucomp_unreduced(&mut r, &self.form, &rhs.form);
+        *self = Self::from_partial(r);
+    }
+}
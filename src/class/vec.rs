@@ -0,0 +1,280 @@
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, SerializationError, Valid, Validate,
+};
+use ark_std::Zero;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::class::{config::ClassConfig, context::ClassGroupContext, ClassGroup};
+
+// Structure-of-arrays batch of forms, with the coefficients a, b and c each in their own vector.
+// The batch operations load forms into per-thread scratch forms and contexts, so they only
+// allocate their outputs. Serialization writes the length once, followed by all a, all b and all c.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct ClassGroupVec<T: ClassConfig> {
+    pub a: Vec<T::Int>,
+    pub b: Vec<T::Int>,
+    pub c: Vec<T::Int>,
+}
+
+// Per-thread state of the batch operations
+#[derive(Default)]
+struct Scratch<T: ClassConfig> {
+    ctx: ClassGroupContext<T>,
+    f1: ClassGroup<T>,
+    f2: ClassGroup<T>,
+}
+
+impl<T: ClassConfig> ClassGroupVec<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            a: Vec::with_capacity(n),
+            b: Vec::with_capacity(n),
+            c: Vec::with_capacity(n),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.a.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.a.is_empty()
+    }
+
+    pub fn push(&mut self, f: ClassGroup<T>) {
+        self.a.push(f.a);
+        self.b.push(f.b);
+        self.c.push(f.c);
+    }
+
+    // Copy of the i-th form
+    pub fn get(&self, i: usize) -> ClassGroup<T> {
+        ClassGroup::new_unchecked(self.a[i].clone(), self.b[i].clone(), self.c[i].clone())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ClassGroup<T>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    // f <- i-th form, reusing the allocations of f
+    fn load(&self, i: usize, f: &mut ClassGroup<T>) {
+        f.a.clone_from(&self.a[i]);
+        f.b.clone_from(&self.b[i]);
+        f.c.clone_from(&self.c[i]);
+    }
+
+    // op(scratch, i) for every index, collected into a new batch
+    fn map<F>(&self, op: F) -> Self
+    where
+        F: Fn(&mut Scratch<T>, usize) -> ClassGroup<T> + Sync + Send,
+    {
+        #[cfg(feature = "parallel")]
+        {
+            let forms: Vec<ClassGroup<T>> =
+                (0..self.len()).into_par_iter().map_init(Scratch::default, op).collect();
+            forms.into_iter().collect()
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            let mut scratch = Scratch::default();
+            (0..self.len()).map(|i| op(&mut scratch, i)).collect()
+        }
+    }
+
+    // self[i] + other[i]
+    pub fn add(&self, other: &Self) -> Self {
+        assert_eq!(self.len(), other.len(), "batches must have the same length");
+        self.map(|s, i| {
+            self.load(i, &mut s.f1);
+            other.load(i, &mut s.f2);
+            let mut r = ClassGroup::default();
+            ClassGroup::nucomp_into(&mut s.ctx, &mut r, &s.f1, &s.f2);
+            r
+        })
+    }
+
+    // 2 self[i]
+    pub fn double(&self) -> Self {
+        self.map(|s, i| {
+            self.load(i, &mut s.f1);
+            let mut r = ClassGroup::default();
+            ClassGroup::nudupl_into(&mut s.ctx, &mut r, &s.f1);
+            r
+        })
+    }
+
+    // scalars[i] * self[i]
+    pub fn scalar_mul(&self, scalars: &[T::Int]) -> Self {
+        assert_eq!(self.len(), scalars.len(), "forms and scalars must have the same length");
+        self.map(|s, i| {
+            self.load(i, &mut s.f1);
+            let mut r = ClassGroup::default();
+            ClassGroup::nupow_into(&mut s.ctx, &mut r, &s.f1, &scalars[i]);
+            r
+        })
+    }
+
+    // Reduces every form in place
+    pub fn reduce(&mut self) {
+        type Coefficients<'a, I> = ((&'a mut I, &'a mut I), &'a mut I);
+        let reduce = |ctx: &mut ClassGroupContext<T>, ((a, b), c): Coefficients<T::Int>| {
+            let mut f = ClassGroup::new_unchecked(std::mem::take(a), std::mem::take(b), std::mem::take(c));
+            f.reduce_in_place(ctx);
+            (*a, *b, *c) = (f.a, f.b, f.c);
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            self.a
+                .par_iter_mut()
+                .zip(&mut self.b)
+                .zip(&mut self.c)
+                .for_each_init(ClassGroupContext::new, reduce);
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            let mut ctx = ClassGroupContext::new();
+            self.a
+                .iter_mut()
+                .zip(&mut self.b)
+                .zip(&mut self.c)
+                .for_each(|abc| reduce(&mut ctx, abc));
+        }
+    }
+
+    // Sum of all forms, accumulated per thread straight from the columns, then summed over the threads
+    pub fn sum(&self) -> ClassGroup<T> {
+        // acc + self[i], with f2 taking the previous acc
+        let add = |(mut s, mut acc): (Scratch<T>, ClassGroup<T>), i: usize| {
+            self.load(i, &mut s.f1);
+            ClassGroup::nucomp_into(&mut s.ctx, &mut s.f2, &acc, &s.f1);
+            std::mem::swap(&mut acc, &mut s.f2);
+            (s, acc)
+        };
+        let init = || (Scratch::default(), ClassGroup::zero());
+
+        #[cfg(feature = "parallel")]
+        {
+            (0..self.len())
+                .into_par_iter()
+                .fold(init, add)
+                .map(|(_, acc)| acc)
+                .reduce(ClassGroup::zero, |x, y| x + &y)
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            (0..self.len()).fold(init(), add).1
+        }
+    }
+}
+
+impl<T: ClassConfig> FromIterator<ClassGroup<T>> for ClassGroupVec<T> {
+    fn from_iter<I: IntoIterator<Item = ClassGroup<T>>>(iter: I) -> Self {
+        let mut v = Self::new();
+        for f in iter {
+            v.push(f);
+        }
+        v
+    }
+}
+
+impl<T: ClassConfig> From<Vec<ClassGroup<T>>> for ClassGroupVec<T> {
+    fn from(forms: Vec<ClassGroup<T>>) -> Self {
+        forms.into_iter().collect()
+    }
+}
+
+impl<T: ClassConfig> From<ClassGroupVec<T>> for Vec<ClassGroup<T>> {
+    fn from(v: ClassGroupVec<T>) -> Self {
+        v.a.into_iter()
+            .zip(v.b)
+            .zip(v.c)
+            .map(|((a, b), c)| ClassGroup::new_unchecked(a, b, c))
+            .collect()
+    }
+}
+
+// Serialization
+// Length, then the a, b and c columns
+impl<T: ClassConfig> CanonicalSerialize for ClassGroupVec<T> {
+    fn serialize_with_mode<W: ark_std::io::Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        (self.len() as u64).serialize_with_mode(&mut writer, compress)?;
+        for x in self.a.iter().chain(&self.b).chain(&self.c) {
+            x.serialize_with_mode(&mut writer, compress)?;
+        }
+        Ok(())
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        let coefficients: usize = self.a.iter()
+            .chain(&self.b)
+            .chain(&self.c)
+            .map(|x| x.serialized_size(compress))
+            .sum();
+        (self.len() as u64).serialized_size(compress) + coefficients
+    }
+}
+
+impl<T: ClassConfig> CanonicalDeserialize for ClassGroupVec<T> {
+    fn deserialize_with_mode<R: ark_std::io::Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let len = u64::deserialize_with_mode(&mut reader, compress, validate)? as usize;
+
+        // No preallocation, the length is not trusted
+        let mut column = || -> Result<Vec<T::Int>, SerializationError> {
+            (0..len)
+                .map(|_| T::Int::deserialize_with_mode(&mut reader, compress, Validate::No))
+                .collect()
+        };
+        let a = column()?;
+        let b = column()?;
+        let c = column()?;
+
+        let v = Self { a, b, c };
+        if validate == Validate::Yes {
+            v.check()?;
+        }
+        Ok(v)
+    }
+}
+
+// Every form must be valid as a ClassGroup, checked in parallel with the parallel feature
+impl<T: ClassConfig> Valid for ClassGroupVec<T> {
+    fn check(&self) -> Result<(), SerializationError> {
+        if self.b.len() != self.len() || self.c.len() != self.len() {
+            return Err(SerializationError::InvalidData);
+        }
+
+        let check = |f: &mut ClassGroup<T>, i: usize| {
+            self.load(i, f);
+            f.check()
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            (0..self.len()).into_par_iter().try_for_each_init(ClassGroup::zero, check)
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            let mut f = ClassGroup::zero();
+            (0..self.len()).try_for_each(|i| check(&mut f, i))
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::Integer;
+
+// Trial division bound of factor, larger factors are split with Pollard rho
+const TRIAL_DIVISION_BOUND: u64 = 1 << 16;
+
+// Rounds of the primality test on cofactors
+const FACTOR_PRIME_REPS: u32 = 30;
+
+pub(crate) fn gcd<I: Integer>(a: &I, b: &I) -> I {
+    let mut g = a.clone();
+    let mut s = b.clone();
+    let mut t = I::zero();
+    g.extended_gcd_mut(&mut s, &mut t);
+    g
+}
+
+// Nontrivial factor of an odd composite n, Pollard rho with Brent's cycle finding on x^2 + c
+fn pollard_rho<I: Integer>(n: &I) -> I {
+    const BATCH: u64 = 128;
+    let one = I::one();
+
+    for c in 1u64.. {
+        let c = I::from(c);
+        let f = |x: &I| (x.clone() * x + &c).rem_floor(n);
+
+        let mut y = I::from(2u64);
+        let mut x = y.clone();
+        let mut ys = y.clone();
+        let mut q = one.clone();
+        let mut g = one.clone();
+        let mut r = 1u64;
+        while g == one {
+            x.clone_from(&y);
+            for _ in 0..r {
+                y = f(&y);
+            }
+
+            // |x - y| multiplied up in batches, with one gcd per batch
+            let mut k = 0;
+            while k < r && g == one {
+                ys.clone_from(&y);
+                for _ in 0..BATCH.min(r - k) {
+                    y = f(&y);
+                    q = (q * (x.clone() - &y).abs()).rem_floor(n);
+                }
+                g = gcd(&q, n);
+                k += BATCH;
+            }
+            r *= 2;
+        }
+
+        // The batch overshot, redo its steps one at a time
+        if g == *n {
+            loop {
+                ys = f(&ys);
+                g = gcd(&(x.clone() - &ys).abs(), n);
+                if g != one {
+                    break;
+                }
+            }
+        }
+
+        if g != *n {
+            return g;
+        }
+    }
+    unreachable!()
+}
+
+// Prime factorization of |n| for n != 0, as (prime, exponent) sorted by prime.
+// Trial division up to TRIAL_DIVISION_BOUND, then Pollard rho, so only practical when the
+// cofactor left by trial division has no two large prime factors.
+pub fn factor<I: Integer>(n: &I) -> Vec<(I, u32)> {
+    assert!(!n.is_zero(), "cannot factor zero");
+    let mut n = n.abs();
+    let mut primes = Vec::new();
+
+    let mut p = 2u64;
+    while p < TRIAL_DIVISION_BOUND && I::from(p * p) <= n {
+        let q = I::from(p);
+        while n.is_divisible(&q) {
+            n.div_exact(&q);
+            primes.push(q.clone());
+        }
+        p += if p == 2 { 1 } else { 2 };
+    }
+
+    let mut stack = vec![n];
+    while let Some(m) = stack.pop() {
+        if m.is_one() {
+            continue;
+        }
+        if m.is_probably_prime(FACTOR_PRIME_REPS) {
+            primes.push(m);
+            continue;
+        }
+
+        let d = pollard_rho(&m);
+        let mut e = m;
+        e.div_exact(&d);
+        stack.push(d);
+        stack.push(e);
+    }
+
+    primes.sort();
+    let mut factors: Vec<(I, u32)> = Vec::new();
+    for q in primes {
+        match factors.last_mut() {
+            Some((r, e)) if *r == q => *e += 1,
+            _ => factors.push((q, 1)),
+        }
+    }
+    factors
+}
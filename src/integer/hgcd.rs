@@ -0,0 +1,259 @@
+use crate::Integer;
+
+// Below this size the half gcd runs Lehmer steps without recursing. Bases from 1024 to 4096 bits are
+// within noise of each other in benches/hgcd.rs and 512 is 10 to 20% slower. The recursion beats Lehmer
+// steps only by 10 to 20% from about 6000 bits, and by 2x at 32768 bits.
+const HGCD_BASE_BITS: u32 = 1024;
+
+// partial_xgcd stops jumping with half gcds once the remainder is this close to the bound
+const HGCD_MARGIN_BITS: u32 = 64;
+
+// How far above half size the second recursive call of the half gcd stops
+const HGCD_SLACK_BITS: u32 = 4;
+
+// Size of the leading words of the Lehmer steps in the base case of the half gcd
+const LEHMER_BITS: u32 = 62;
+
+// State of Euclid on (a, b) after a partial extended gcd, see Integer::partial_xgcd.
+// The remainders are r_0 = a, r_1 = b, r_{i+1} = r_{i-1} - q_i * r_i with q_i = floor(r_{i-1} / r_i),
+// and the cofactors satisfy r_i = s_i * a + t_i * b. Fields hold the values at i = steps and i = steps + 1.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PartialXgcd<I> {
+    pub r0: I,
+    pub r1: I,
+    pub s0: I,
+    pub t0: I,
+    pub s1: I,
+    pub t1: I,
+    pub steps: usize,
+}
+
+// Product of quotient matrices [[q, 1], [1, 0]] with q >= 1, so that (a, b) = M (r_z, r_{z+1}).
+// Entries are non-negative with m00 >= m01, m10 >= m11 and m00 >= m10, det = (-1)^z.
+#[derive(Clone, Debug)]
+struct Matrix<I> {
+    m00: I,
+    m01: I,
+    m10: I,
+    m11: I,
+}
+
+impl<I: Integer> Matrix<I> {
+    fn identity() -> Self {
+        Self {
+            m00: I::one(),
+            m01: I::zero(),
+            m10: I::zero(),
+            m11: I::one(),
+        }
+    }
+
+    // self <- self * [[q, 1], [1, 0]]
+    fn push(&mut self, q: &I) {
+        std::mem::swap(&mut self.m00, &mut self.m01);
+        self.m00.add_mul(q, &self.m01);
+        std::mem::swap(&mut self.m10, &mut self.m11);
+        self.m10.add_mul(q, &self.m11);
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let entry = |x0: &I, y0: &I, x1: &I, y1: &I| {
+            let mut r = I::zero();
+            r.assign_mul(x0, y0);
+            r.add_mul(x1, y1);
+            r
+        };
+        Self {
+            m00: entry(&self.m00, &other.m00, &self.m01, &other.m10),
+            m01: entry(&self.m00, &other.m01, &self.m01, &other.m11),
+            m10: entry(&self.m10, &other.m00, &self.m11, &other.m10),
+            m11: entry(&self.m10, &other.m01, &self.m11, &other.m11),
+        }
+    }
+
+    // M^-1 (a, b) for a product of z quotient matrices
+    fn apply_inverse(&self, a: &I, b: &I, z: usize) -> (I, I) {
+        let mut alpha = I::zero();
+        alpha.assign_mul(&self.m11, a);
+        alpha.sub_mul(&self.m01, b);
+        let mut beta = I::zero();
+        beta.assign_mul(&self.m00, b);
+        beta.sub_mul(&self.m10, a);
+        if z % 2 == 1 {
+            alpha.neg_in_place();
+            beta.neg_in_place();
+        }
+        (alpha, beta)
+    }
+
+    // Whether (alpha, beta) = M^-1 (a, b) stays a Euclid state, i.e. alpha > beta >= 0, for any
+    // (a + e0, b + e1) with e0, e1 in [0, 1). The perturbation moves alpha by less than m01 and
+    // beta by less than m00, so the quotients of M are then also those of the full numbers a
+    // and b are the leading bits of.
+    fn certifies(&self, alpha: &I, beta: &I) -> bool {
+        *beta >= self.m00 && *alpha >= beta.clone() + &self.m00 + &self.m01
+    }
+}
+
+// Certified single division steps on (alpha, beta), small enough for i128, as in Matrix::certifies.
+// Returns the entries m00, m01, m10, m11 of the matrix and the number of steps.
+fn euclid_word(mut alpha: i128, mut beta: i128) -> ([i128; 4], usize) {
+    let [mut m00, mut m01, mut m10, mut m11] = [1, 0, 0, 1];
+    let mut z = 0;
+    while beta != 0 {
+        let (q, r) = (alpha / beta, alpha % beta);
+        let n00 = m01 + q * m00;
+        if r < n00 || beta < r + n00 + m00 {
+            break;
+        }
+
+        (m00, m01, m10, m11) = (n00, m00, m11 + q * m10, m10);
+        (alpha, beta) = (beta, r);
+        z += 1;
+    }
+    ([m00, m01, m10, m11], z)
+}
+
+// Single division steps on (alpha, beta) as long as the state stays certified.
+// Lehmer: the steps run on the leading LEHMER_BITS of alpha and beta first, which certifies them for
+// (alpha, beta) as well, and are only applied to the full numbers once per batch.
+fn euclid_certified<I: Integer>(m: &mut Matrix<I>, alpha: &mut I, beta: &mut I, z: &mut usize) {
+    while !beta.is_zero() && alpha.significant_bits() > LEHMER_BITS {
+        let k = alpha.significant_bits() - LEHMER_BITS;
+        let (w, zw) = euclid_word(alpha.shr_to_i64_wrapping(k) as i128, beta.shr_to_i64_wrapping(k) as i128);
+        if zw == 0 {
+            break;
+        }
+
+        // The batch must also keep the state certified for the numbers (alpha, beta) are the leading bits of
+        let [m00, m01, m10, m11] = w.map(I::from);
+        let mw = Matrix { m00, m01, m10, m11 };
+        let (alpha1, beta1) = mw.apply_inverse(alpha, beta, zw);
+        let m1 = m.mul(&mw);
+        if !m1.certifies(&alpha1, &beta1) {
+            break;
+        }
+        (*m, *alpha, *beta) = (m1, alpha1, beta1);
+        *z += zw;
+    }
+
+    let mut q = I::zero();
+    let mut r = I::zero();
+    while !beta.is_zero() {
+        q.clone_from(alpha);
+        r.clone_from(beta);
+        q.div_rem_floor_mut(&mut r);
+
+        // New state (beta, r) with m00 <- q * m00 + m01 and m01 <- m00
+        let mut m00 = m.m01.clone();
+        m00.add_mul(&q, &m.m00);
+        if r < m00 || *beta < r.clone() + &m00 + &m.m00 {
+            break;
+        }
+
+        m.push(&q);
+        std::mem::swap(alpha, beta);
+        std::mem::swap(beta, &mut r);
+        *z += 1;
+    }
+}
+
+// Half gcd: Euclid steps on (a, b), a >= b >= 0, certified as in Matrix::certifies, which takes
+// a down to about half its size. Runs in O(M(n) log n) through two recursive calls on the leading
+// halves. Returns M, the reduced (alpha, beta) and the number of steps.
+fn hgcd<I: Integer>(a: &I, b: &I, base_bits: u32) -> (Matrix<I>, I, I, usize) {
+    let mut m = Matrix::identity();
+    let mut alpha = a.clone();
+    let mut beta = b.clone();
+    let mut z = 0;
+
+    let n = a.significant_bits();
+    if n > base_bits {
+        // First half on the leading n - k bits, certified there so valid for (a, b)
+        let k = n / 2;
+        let (m1, _, _, z1) = hgcd(&(a.clone() >> k), &(b.clone() >> k), base_bits);
+        if z1 > 0 {
+            let (alpha1, beta1) = m1.apply_inverse(a, b, z1);
+            if m1.certifies(&alpha1, &beta1) {
+                (m, alpha, beta, z) = (m1, alpha1, beta1, z1);
+            }
+        }
+
+        // Second half on the leading bits of (alpha, beta), aiming at alpha a little above n / 2 bits
+        // so that the combined matrix still certifies
+        let n2 = alpha.significant_bits();
+        if n2 > k + HGCD_SLACK_BITS + 2 && n2 < n && !beta.is_zero() {
+            let k2 = n - n2 + 2 * HGCD_SLACK_BITS;
+            let (m2, _, _, z2) = hgcd(&(alpha.clone() >> k2), &(beta.clone() >> k2), base_bits);
+            if z2 > 0 {
+                let (alpha2, beta2) = m2.apply_inverse(&alpha, &beta, z2);
+                let m12 = m.mul(&m2);
+                if m12.certifies(&alpha2, &beta2) {
+                    (m, alpha, beta, z) = (m12, alpha2, beta2, z + z2);
+                }
+            }
+        }
+    }
+
+    euclid_certified(&mut m, &mut alpha, &mut beta, &mut z);
+    (m, alpha, beta, z)
+}
+
+// Euclid on (a, b), a > b >= 0, until the first remainder r_z <= bound or r_{z+1} = 0.
+// Far from the bound it jumps with half gcds on the leading bits, which are exact Euclid steps
+// for the full numbers, then finishes with single steps.
+pub(crate) fn partial_xgcd<I: Integer>(a: &I, b: &I, bound: &I) -> PartialXgcd<I> {
+    partial_xgcd_with_base_bits(a, b, bound, HGCD_BASE_BITS)
+}
+
+// partial_xgcd with the half gcds recursing down to base_bits instead of HGCD_BASE_BITS, u32::MAX
+// for Lehmer steps only. For tuning HGCD_BASE_BITS in benches/hgcd.rs.
+pub fn partial_xgcd_with_base_bits<I: Integer>(a: &I, b: &I, bound: &I, base_bits: u32) -> PartialXgcd<I> {
+    let mut m = Matrix::identity();
+    let mut alpha = a.clone();
+    let mut beta = b.clone();
+    let mut z = 0;
+
+    let mut q = I::zero();
+    let mut r = I::zero();
+    while alpha > *bound && !beta.is_zero() {
+        let n = alpha.significant_bits();
+        let gap = n - bound.significant_bits().min(n);
+        if gap > HGCD_MARGIN_BITS {
+            // The half gcd on the leading p bits takes alpha down by about p / 2 bits
+            let p = (2 * (gap - HGCD_MARGIN_BITS)).min(n);
+            let k = n - p;
+            let (m1, _, _, z1) = hgcd(&(alpha.clone() >> k), &(beta.clone() >> k), base_bits);
+            if z1 > 0 {
+                // Only take the jump if no remainder on the way got below the bound
+                let (alpha1, beta1) = m1.apply_inverse(&alpha, &beta, z1);
+                if alpha1 > *bound {
+                    m = m.mul(&m1);
+                    (alpha, beta) = (alpha1, beta1);
+                    z += z1;
+                    continue;
+                }
+            }
+        }
+
+        q.clone_from(&alpha);
+        r.clone_from(&beta);
+        q.div_rem_floor_mut(&mut r);
+        m.push(&q);
+        std::mem::swap(&mut alpha, &mut beta);
+        std::mem::swap(&mut beta, &mut r);
+        z += 1;
+    }
+
+    // r_z = (-1)^z (m11 a - m01 b) and r_{z+1} = (-1)^z (m00 b - m10 a)
+    let Matrix { m00, m01, m10, m11 } = m;
+    let (mut s0, mut t0, mut s1, mut t1) = (m11, -m01, -m10, m00);
+    if z % 2 == 1 {
+        s0.neg_in_place();
+        t0.neg_in_place();
+        s1.neg_in_place();
+        t1.neg_in_place();
+    }
+
+    PartialXgcd { r0: alpha, r1: beta, s0, t0, s1, t1, steps: z }
+}
@@ -1,5 +1,9 @@
-use std::ops::Shr;
+use std::{ops::Shr, sync::atomic};
 
+pub mod factor;
+pub mod hgcd;
+
+use bincode::Options;
 use serde::{Deserialize, Serialize};
 use ark_serialize::{
     CanonicalDeserialize, CanonicalDeserializeWithFlags, 
@@ -20,9 +24,11 @@ use ark_std::{
 };
 
 use rug::{
+    Assign,
     Complete, 
     Integer as RugInteger,
-    ops::Pow
+    integer::IsPrime,
+    ops::{NegAssign, Pow, RemRounding, RemRoundingAssign}
 };
 use crate::{
     AdditiveGroup, 
@@ -30,56 +36,229 @@ use crate::{
 };
 
 // Implement the integer trait for ZZ
-#[derive(Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[derive(Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct ZZ {
     pub value: RugInteger,
 }
 
-// Additional useful methods
-impl ZZ {
-    pub fn is_odd(&self) -> bool {
+// clone_from reuses the allocation of self, which the derive would not
+impl Clone for ZZ {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.value.clone_from(&source.value);
+    }
+}
+
+impl AdditiveGroup for ZZ {
+    type Scalar = Self;
+
+    fn neg_in_place(&mut self) -> &mut Self {
+        self.value.neg_assign();
+        self
+    }
+}
+
+// Integer arithmetic beyond the group ops, backed by rug
+impl Integer for ZZ {
+    fn is_odd(&self) -> bool {
         self.value.is_odd()
     }
 
-    pub fn modulus(&self, other: &Self) -> Self {
+    fn modulus(&self, other: &Self) -> Self {
         Self {
             value: self.value.clone() % &other.value,
         }
     }
 
-    pub fn pow(&self, other: &u32) -> Self {
+    fn pow(&self, other: &u32) -> Self {
         Self {
             value: self.value.clone().pow(other),
         }
     }
 
-    pub fn div_rem_ceil_mut(&mut self, divisor: &mut Self) {
+    fn div_rem_ceil_mut(&mut self, divisor: &mut Self) {
         self.value.div_rem_ceil_mut(&mut divisor.value);
     }
 
-    pub fn div_rem_floor_mut(&mut self, divisor: &mut Self) {
+    fn div_rem_floor_mut(&mut self, divisor: &mut Self) {
         self.value.div_rem_floor_mut(&mut divisor.value);
     }
 
-    pub fn extended_gcd_mut(&mut self, other: &mut Self, rop: &mut Self) {
+    fn extended_gcd_mut(&mut self, other: &mut Self, rop: &mut Self) {
         self.value.extended_gcd_mut(&mut other.value, &mut rop.value);
     }
 
-    pub fn div_exact(&mut self, other: &Self) {
+    fn div_exact(&mut self, other: &Self) {
         self.value.div_exact_mut(&other.value);
-    } 
-}
+    }
+
+    fn rem_floor(&self, other: &Self) -> Self {
+        Self {
+            value: self.value.clone().rem_floor(&other.value),
+        }
+    }
 
+    fn is_divisible(&self, other: &Self) -> bool {
+        self.value.is_divisible(&other.value)
+    }
 
-impl AdditiveGroup for ZZ {
-    type Scalar = Self;
+    fn abs(&self) -> Self {
+        Self {
+            value: self.value.clone().abs(),
+        }
+    }
+
+    fn significant_bits(&self) -> u32 {
+        self.value.significant_bits()
+    }
+
+    fn get_bit(&self, index: u32) -> bool {
+        self.value.get_bit(index)
+    }
+
+    fn shr_to_i64_wrapping(&self, shift: u32) -> i64 {
+        // Read the limbs of |self| directly, this runs in every reduction step and must not allocate
+        let limbs = self.value.as_limbs();
+        let Some(first) = limbs.first() else {
+            return 0;
+        };
+        let limb_bits = 8 * std::mem::size_of_val(first) as u32;
+
+        let mut v = 0u64;
+        let mut filled = 0;
+        let mut bit = shift;
+        while filled < 64 {
+            let Some(&limb) = limbs.get((bit / limb_bits) as usize) else {
+                break;
+            };
+            let offset = bit % limb_bits;
+            // Limbs are only 32 bits on some targets
+            #[allow(clippy::unnecessary_cast)]
+            let limb = limb as u64;
+            v |= (limb >> offset) << filled;
+            filled += limb_bits - offset;
+            bit += limb_bits - offset;
+        }
+
+        if self.value < 0 {
+            (v as i64).wrapping_neg()
+        } else {
+            v as i64
+        }
+    }
+
+    fn assign_i64(&mut self, v: i64) {
+        self.value.assign(v);
+    }
+
+    fn assign_mul(&mut self, x: &Self, y: &Self) {
+        self.value.assign(&x.value * &y.value);
+    }
+
+    fn add_mul(&mut self, x: &Self, y: &Self) {
+        self.value += &x.value * &y.value;
+    }
+
+    fn sub_mul(&mut self, x: &Self, y: &Self) {
+        self.value -= &x.value * &y.value;
+    }
+
+    fn rem_floor_mut(&mut self, other: &Self) {
+        self.value.rem_floor_assign(&other.value);
+    }
+
+    fn is_probably_prime(&self, reps: u32) -> bool {
+        self.value.is_probably_prime(reps) != IsPrime::No
+    }
+
+    fn kronecker(&self, n: &Self) -> i32 {
+        self.value.kronecker(&n.value)
+    }
+
+    fn pow_mod(&self, e: &Self, m: &Self) -> Self {
+        Self {
+            value: self.value.pow_mod_ref(&e.value, &m.value).expect("negative exponent").into(),
+        }
+    }
 }
 
-impl Integer for ZZ { }
+// Tonelli-Shanks, see Integer::sqrt_mod_prime
+pub(crate) fn sqrt_mod_prime<I: Integer>(n: &I, p: &I) -> Option<I> {
+    let n = n.rem_floor(p);
+    if n.is_zero() {
+        return Some(n);
+    }
+    if n.kronecker(p) != 1 {
+        return None;
+    }
+
+    // p - 1 = q 2^s with q odd
+    let one = I::one();
+    let mut q = p.clone() - &one;
+    let mut s = 0;
+    while !q.is_odd() {
+        q = q >> 1;
+        s += 1;
+    }
+
+    // p = 3 mod 4: n^((p + 1) / 4)
+    if s == 1 {
+        return Some(n.pow_mod(&((p.clone() + &one) >> 2), p));
+    }
+
+    let mut z = I::from(2u8);
+    while z.kronecker(p) != -1 {
+        z += &one;
+    }
+
+    // Invariants r^2 = n t, c has order 2^m and t has order dividing 2^(m - 1)
+    let mut m = s;
+    let mut c = z.pow_mod(&q, p);
+    let mut t = n.pow_mod(&q, p);
+    let mut r = n.pow_mod(&((q + &one) >> 1), p);
+    let square = |x: &I| (x.clone() * x).rem_floor(p);
+    while !t.is_one() {
+        // Least i with t^(2^i) = 1
+        let mut i = 0;
+        let mut t2 = t.clone();
+        while !t2.is_one() {
+            t2 = square(&t2);
+            i += 1;
+        }
+
+        let mut b = c;
+        for _ in 0..m - i - 1 {
+            b = square(&b);
+        }
+        m = i;
+        c = square(&b);
+        t = (t * &c).rem_floor(p);
+        r = (r * &b).rem_floor(p);
+    }
+    Some(r)
+}
 
 // Serialization
 // Uses serde serialization
 // No compression
+
+// Bound on the encoded size of a ZZ accepted by deserialization, so that a forged length prefix
+// fails instead of allocating that much
+pub const MAX_SERIALIZED_BYTES: u64 = 1 << 20;
+
+// The options of bincode::serialize, with the size limit
+fn bincode_options() -> impl bincode::Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_SERIALIZED_BYTES)
+}
+
 impl CanonicalSerialize for ZZ {
     fn serialize_with_mode<W: ark_std::io::Write>(
         &self,
@@ -103,9 +282,10 @@ impl CanonicalDeserialize for ZZ {
         _compress: Compress,
         _validate: Validate,
     ) -> Result<Self, ark_serialize::SerializationError> {
-        let mut bytes = Vec::new();
-        reader.read_to_end(&mut bytes)?;
-        let value = bincode::deserialize(&bytes).unwrap();
+        // bincode is self-delimiting, so only this value's bytes are consumed
+        let value = bincode_options()
+            .deserialize_from(&mut reader)
+            .map_err(|_| ark_serialize::SerializationError::InvalidData)?;
         Ok(value)
     }
 }
@@ -126,7 +306,7 @@ impl CanonicalSerializeWithFlags for ZZ {
 
     fn serialized_size_with_flags<F: Flags>(&self) -> usize {
         let bytes = bincode::serialize(self).unwrap();
-        bytes.len()
+        bytes.len() + 1
     }
 }
 
@@ -134,12 +314,13 @@ impl CanonicalDeserializeWithFlags for ZZ {
     fn deserialize_with_flags<R: ark_std::io::Read, F: Flags>(
         mut reader: R,
     ) -> Result<(Self, F), ark_serialize::SerializationError> {
-        let mut bytes = Vec::new();
-        reader.read_to_end(&mut bytes)?;
+        let value = bincode_options()
+            .deserialize_from(&mut reader)
+            .map_err(|_| ark_serialize::SerializationError::InvalidData)?;
 
-        let value = bincode::deserialize(&bytes[..bytes.len() - 1]).unwrap();
-        let flag_byte = bytes[bytes.len() - 1];
-        let flags = F::from_u8(flag_byte).unwrap();
+        let mut flag_byte = [0u8; 1];
+        reader.read_exact(&mut flag_byte)?;
+        let flags = F::from_u8(flag_byte[0]).ok_or(ark_serialize::SerializationError::UnexpectedFlags)?;
         Ok((value, flags))
     }
 }
@@ -185,11 +366,30 @@ impl One for ZZ {
             value: RugInteger::from(1),
         }
     }
+
+    fn is_one(&self) -> bool {
+        self.value == 1
+    }
 }
 
+// Overwrites the whole limb buffer in place, including limbs above the current size left over from
+// larger earlier values, and only then releases it. Copies GMP made while computing the value,
+// e.g. when reallocating, are out of reach.
 impl Zeroize for ZZ {
     fn zeroize(&mut self) {
-        self.value = RugInteger::from(0);
+        let raw = self.value.as_raw_mut();
+
+        // SAFETY: d points to alloc limbs owned by this integer, and size 0 is a valid value of 0
+        unsafe {
+            let limbs = (*raw).d.as_ptr();
+            for i in 0..(*raw).alloc as usize {
+                std::ptr::write_volatile(limbs.add(i), 0);
+            }
+            (*raw).size = 0;
+        }
+        atomic::compiler_fence(atomic::Ordering::SeqCst);
+
+        self.value.shrink_to_fit();
     }
 }
 
@@ -384,6 +584,47 @@ impl<'a> Product<&'a Self> for ZZ {
     }
 }
 
+// Ops on references, which allocate only the result
+impl Neg for &ZZ {
+    type Output = ZZ;
+
+    fn neg(self) -> Self::Output {
+        ZZ {
+            value: (-&self.value).complete(),
+        }
+    }
+}
+
+impl Add<&ZZ> for &ZZ {
+    type Output = ZZ;
+
+    fn add(self, other: &ZZ) -> Self::Output {
+        ZZ {
+            value: (&self.value + &other.value).complete(),
+        }
+    }
+}
+
+impl Sub<&ZZ> for &ZZ {
+    type Output = ZZ;
+
+    fn sub(self, other: &ZZ) -> Self::Output {
+        ZZ {
+            value: (&self.value - &other.value).complete(),
+        }
+    }
+}
+
+impl Mul<&ZZ> for &ZZ {
+    type Output = ZZ;
+
+    fn mul(self, other: &ZZ) -> Self::Output {
+        ZZ {
+            value: (&self.value * &other.value).complete(),
+        }
+    }
+}
+
 // From
 impl From<u64> for ZZ {
     fn from(value: u64) -> Self {
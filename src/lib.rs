@@ -9,16 +9,21 @@ use ark_std::{
     fmt::{Debug, Display},
     hash::Hash,
     iter::*,
-    ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+    ops::{Add, AddAssign, Mul, MulAssign, Neg, Shr, Sub, SubAssign},
     UniformRand,
 };
 
 
 pub mod class;
 pub mod integer;
+pub mod msm;
+pub mod secret;
+pub mod shake;
+
+use integer::hgcd::PartialXgcd;
+
+use ark_std::cfg_iter;
 
-#[cfg(feature = "parallel")]
-use ark_std::cmp::max;
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
@@ -83,8 +88,82 @@ pub trait AdditiveGroup:
         *self = -(self.clone());
         self
     }
+
+    // Batch operations, spread over rayon threads with the parallel feature
+
+    // a[i] + b[i]
+    fn batch_add(a: &[Self], b: &[Self]) -> Vec<Self> {
+        assert_eq!(a.len(), b.len(), "batches must have the same length");
+        cfg_iter!(a).zip(cfg_iter!(b)).map(|(x, y)| x.clone() + y).collect()
+    }
+
+    // 2 a[i]
+    fn batch_double(a: &[Self]) -> Vec<Self> {
+        cfg_iter!(a).map(|x| x.double()).collect()
+    }
+
+    // scalars[i] * bases[i]
+    fn batch_scalar_mul(bases: &[Self], scalars: &[Self::Scalar]) -> Vec<Self> {
+        assert_eq!(bases.len(), scalars.len(), "bases and scalars must have the same length");
+        cfg_iter!(bases).zip(cfg_iter!(scalars)).map(|(x, s)| x.clone() * s).collect()
+    }
+
+    // Brings every element into its canonical representation. The group operations already
+    // return canonical elements, so this only matters for ones built by hand or deserialized
+    // unchecked, and is a no-op for groups without non-canonical representations
+    fn batch_reduce(_elems: &mut [Self]) {}
+
+    // Sum by pairwise tree reduction, with the two halves summed in parallel
+    fn sum_tree(elems: &[Self]) -> Self {
+        match elems {
+            [] => Self::zero(),
+            [x] => x.clone(),
+            _ => {
+                let (l, r) = elems.split_at(elems.len() / 2);
+                let (l, r) = join(|| Self::sum_tree(l), || Self::sum_tree(r));
+                l + r
+            }
+        }
+    }
 }
 
+// rayon::join with the parallel feature, otherwise a then b
+fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        rayon::join(a, b)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        (a(), b())
+    }
+}
+
+// Arithmetic on references, implemented for &ZZ and &ClassGroup through the blanket impl below.
+// Bounds on &Self are not implied by a bound on the trait itself, so generic code asks for them:
+// fn f<G: AdditiveGroup>(x: &G, y: &G) -> G where for<'a> &'a G: AdditiveGroupRef<'a, G> { x + y }
+pub trait AdditiveGroupRef<'a, G: AdditiveGroup + 'a>:
+    Neg<Output = G>
+    + Add<&'a G, Output = G>
+    + Sub<&'a G, Output = G>
+    + Mul<&'a <G as AdditiveGroup>::Scalar, Output = G>
+{
+}
+
+impl<'a, G: AdditiveGroup + 'a, R> AdditiveGroupRef<'a, G> for R where
+    R: Neg<Output = G>
+        + Add<&'a G, Output = G>
+        + Sub<&'a G, Output = G>
+        + Mul<&'a <G as AdditiveGroup>::Scalar, Output = G>
+{
+}
 
 pub trait Integer:
     'static
@@ -121,6 +200,82 @@ pub trait Integer:
     + From<i8>
     + From<bool>
     + Product<Self>
+    + Shr<u32, Output = Self>
 {
-    
-}
\ No newline at end of file
+    fn is_odd(&self) -> bool;
+
+    // Remainder with the sign of self
+    fn modulus(&self, other: &Self) -> Self;
+
+    fn pow(&self, other: &u32) -> Self;
+
+    // self <- ceil(self / divisor), divisor <- remainder
+    fn div_rem_ceil_mut(&mut self, divisor: &mut Self);
+
+    // self <- floor(self / divisor), divisor <- remainder
+    fn div_rem_floor_mut(&mut self, divisor: &mut Self);
+
+    // self <- gcd(self, other) = other * self + rop * other
+    fn extended_gcd_mut(&mut self, other: &mut Self, rop: &mut Self);
+
+    // self <- self / other, other must divide self
+    fn div_exact(&mut self, other: &Self);
+
+    // Remainder with the sign of other
+    fn rem_floor(&self, other: &Self) -> Self;
+
+    fn is_divisible(&self, other: &Self) -> bool;
+
+    fn abs(&self) -> Self;
+
+    // Number of bits of |self|
+    fn significant_bits(&self) -> u32;
+
+    // Two's complement bit at index
+    fn get_bit(&self, index: u32) -> bool;
+
+    // Low 64 bits of self / 2^shift rounded toward zero, in two's complement
+    fn shr_to_i64_wrapping(&self, shift: u32) -> i64;
+
+    // The following keep the allocation of self, for use on preallocated temporaries
+
+    // self <- v
+    fn assign_i64(&mut self, v: i64);
+
+    // self <- x * y
+    fn assign_mul(&mut self, x: &Self, y: &Self);
+
+    // self <- self + x * y
+    fn add_mul(&mut self, x: &Self, y: &Self);
+
+    // self <- self - x * y
+    fn sub_mul(&mut self, x: &Self, y: &Self);
+
+    // self <- remainder of self / other with the sign of other
+    fn rem_floor_mut(&mut self, other: &Self);
+
+    // Probabilistic primality test with reps rounds, never false for primes
+    fn is_probably_prime(&self, reps: u32) -> bool;
+
+    // Kronecker symbol (self / n)
+    fn kronecker(&self, n: &Self) -> i32;
+
+    // self^e mod m for e >= 0 and m > 0, in [0, m)
+    fn pow_mod(&self, e: &Self, m: &Self) -> Self;
+
+    // Square root of self modulo an odd prime p in [0, p), None if self is not a square mod p
+    fn sqrt_mod_prime(&self, p: &Self) -> Option<Self> {
+        integer::sqrt_mod_prime(self, p)
+    }
+
+    // Extended Euclid on (self, other), self > other >= 0, stopped at the first remainder <= bound.
+    // Quasi-linear through half gcds on the leading bits, see integer::hgcd::PartialXgcd
+    fn partial_xgcd(&self, other: &Self, bound: &Self) -> PartialXgcd<Self> {
+        integer::hgcd::partial_xgcd(self, other, bound)
+    }
+}
+
+// Arithmetic on references for Integer, see AdditiveGroupRef
+pub trait IntegerRef<'a, I: Integer + 'a>: AdditiveGroupRef<'a, I> {}
+
+impl<'a, I: Integer + 'a, R: AdditiveGroupRef<'a, I>> IntegerRef<'a, I> for R {}
\ No newline at end of file
@@ -0,0 +1,215 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use ark_std::{cfg_into_iter, cfg_iter, Zero};
+
+use crate::{AdditiveGroup, Integer};
+
+// Below this many bases Straus interleaving beats Pippenger's buckets
+const STRAUS_THRESHOLD: usize = 16;
+
+// Straus window width
+const STRAUS_WINDOW: usize = 4;
+
+// Variable-base multi-scalar multiplication sum_i scalars[i] * bases[i].
+// Both methods recode the scalars into signed digits, so negative digits only cost a negation,
+// which is free for class groups.
+pub trait VariableBaseMSM: AdditiveGroup {
+    // Picks Straus for few bases and Pippenger otherwise
+    fn msm(bases: &[Self], scalars: &[Self::Scalar]) -> Self {
+        assert_eq!(bases.len(), scalars.len(), "bases and scalars must have the same length");
+
+        if bases.len() < STRAUS_THRESHOLD {
+            Self::msm_straus(bases, scalars)
+        } else {
+            Self::msm_pippenger(bases, scalars)
+        }
+    }
+
+    // Straus interleaving: one shared chain of doublings, with a wNAF table of odd multiples per base
+    fn msm_straus(bases: &[Self], scalars: &[Self::Scalar]) -> Self {
+        assert_eq!(bases.len(), scalars.len(), "bases and scalars must have the same length");
+
+        let digits: Vec<Vec<i64>> = scalars.iter().map(|s| wnaf(&s.abs(), STRAUS_WINDOW)).collect();
+
+        // Odd multiples b, 3b, ..., (2^(w-1) - 1)b of each base, with the sign of the scalar folded in
+        let tables: Vec<Vec<Self>> = bases
+            .iter()
+            .zip(scalars)
+            .map(|(b, s)| {
+                let b = if *s < Self::Scalar::zero() { -b.clone() } else { b.clone() };
+                let b2 = b.double();
+                let mut table = vec![b];
+                for j in 1..(1 << (STRAUS_WINDOW - 2)) {
+                    let t = table[j - 1].clone() + &b2;
+                    table.push(t);
+                }
+                table
+            })
+            .collect();
+
+        let len = digits.iter().map(Vec::len).max().unwrap_or(0);
+        let mut r = Self::zero();
+        for k in (0..len).rev() {
+            if !r.is_zero() {
+                r.double_in_place();
+            }
+
+            for (d, table) in digits.iter().zip(&tables) {
+                match d.get(k) {
+                    Some(&d) if d > 0 => r += &table[(d as usize - 1) / 2],
+                    Some(&d) if d < 0 => r -= &table[(-d) as usize / 2],
+                    _ => {}
+                }
+            }
+        }
+        r
+    }
+
+    // Pippenger's bucket method with signed base 2^c digits, windows are processed in parallel
+    fn msm_pippenger(bases: &[Self], scalars: &[Self::Scalar]) -> Self {
+        assert_eq!(bases.len(), scalars.len(), "bases and scalars must have the same length");
+
+        let c = pippenger_window(bases.len());
+        let max_bits = scalars.iter().map(|s| s.significant_bits()).max().unwrap_or(0);
+        if max_bits == 0 {
+            return Self::zero();
+        }
+
+        // One extra window absorbs the carry of the signed recoding
+        let num_windows = max_bits.div_ceil(c as u32) as usize + 1;
+        let digits: Vec<Vec<i64>> = cfg_iter!(scalars)
+            .map(|s| signed_digits(&s.abs(), c as u32, num_windows))
+            .collect();
+        let bases: Vec<Self> = cfg_iter!(bases)
+            .zip(cfg_iter!(scalars))
+            .map(|(b, s)| if *s < Self::Scalar::zero() { -b.clone() } else { b.clone() })
+            .collect();
+
+        let window_sums: Vec<Self> = cfg_into_iter!(0..num_windows)
+            .map(|k| {
+                // Bucket j collects the bases whose k-th digit is +-(j + 1)
+                let mut buckets: Vec<Option<Self>> = vec![None; 1 << (c - 1)];
+                for (d, b) in digits.iter().zip(&bases) {
+                    let d = d[k];
+                    if d == 0 {
+                        continue;
+                    }
+
+                    let b = if d > 0 { b.clone() } else { -b.clone() };
+                    let j = d.unsigned_abs() as usize - 1;
+                    buckets[j] = Some(match buckets[j].take() {
+                        Some(acc) => acc + b,
+                        None => b,
+                    });
+                }
+
+                // sum_j (j + 1) * bucket_j through running sums from the top bucket down
+                let mut running: Option<Self> = None;
+                let mut sum: Option<Self> = None;
+                for b in buckets.into_iter().rev() {
+                    if let Some(b) = b {
+                        running = Some(match running {
+                            Some(acc) => acc + b,
+                            None => b,
+                        });
+                    }
+                    if let Some(run) = &running {
+                        sum = Some(match sum {
+                            Some(acc) => acc + run,
+                            None => run.clone(),
+                        });
+                    }
+                }
+                sum.unwrap_or_else(Self::zero)
+            })
+            .collect();
+
+        // Horner over the windows, most significant first
+        let mut r = Self::zero();
+        for s in window_sums.into_iter().rev() {
+            if !r.is_zero() {
+                for _ in 0..c {
+                    r.double_in_place();
+                }
+            }
+            r += s;
+        }
+        r
+    }
+}
+
+impl<G: AdditiveGroup> VariableBaseMSM for G {}
+
+// Pippenger window width, roughly ln(n) + 2 as in arkworks
+fn pippenger_window(n: usize) -> usize {
+    if n < 32 {
+        3
+    } else {
+        (ark_std::log2(n) as usize * 69 / 100) + 2
+    }
+}
+
+// Base 2^w digits of n >= 0 in [-2^(w - 1), 2^(w - 1)], least significant first, padded to len digits
+pub(crate) fn signed_digits<I: Integer>(n: &I, w: u32, len: usize) -> Vec<i64> {
+    let half = 1i64 << (w - 1);
+    let mut carry = 0;
+
+    (0..len as u32)
+        .map(|k| {
+            let mut d = (0..w)
+                .filter(|&j| n.get_bit(k * w + j))
+                .fold(carry, |acc, j| acc + (1 << j));
+            carry = 0;
+            if d > half {
+                d -= 1 << w;
+                carry = 1;
+            }
+            d
+        })
+        .collect()
+}
+
+// Width-w non-adjacent form of n >= 0, least significant digit first.
+// Every non-zero digit is odd with absolute value below 2^(w-1), and is followed by at least w - 1 zeros.
+pub(crate) fn wnaf<I: Integer>(n: &I, w: usize) -> Vec<i64> {
+    let len = n.significant_bits() as usize;
+    let mut bits: Vec<bool> = (0..len + w + 1).map(|i| i < len && n.get_bit(i as u32)).collect();
+    let mut digits = vec![0i64; len + 1];
+
+    let mut i = 0;
+    while i <= len {
+        if !bits[i] {
+            i += 1;
+            continue;
+        }
+
+        // Window value v of bits i..i+w, mapped to the signed residue of v mod 2^w
+        let v = (0..w).filter(|&j| bits[i + j]).fold(0i64, |acc, j| acc | (1 << j));
+        let mut d = v;
+        if v >= 1 << (w - 1) {
+            d -= 1 << w;
+        }
+        digits[i] = d;
+
+        // n -= d * 2^i clears the window, and carries one into bit i + w for negative digits
+        for b in bits.iter_mut().skip(i).take(w) {
+            *b = false;
+        }
+        if d < 0 {
+            let mut j = i + w;
+            while bits[j] {
+                bits[j] = false;
+                j += 1;
+            }
+            bits[j] = true;
+        }
+
+        i += w;
+    }
+
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
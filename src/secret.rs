@@ -0,0 +1,66 @@
+use ark_std::{
+    fmt::{Debug, Formatter},
+    ops::Deref,
+};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::integer::ZZ;
+
+// Key material that is zeroized when dropped, e.g. secret exponents and CL secret keys.
+// Debug does not print the value. Values computed from the secret are not covered, so results that
+// are secret themselves should go into a Secret as well.
+#[derive(Clone, Default)]
+pub struct Secret<S: Zeroize>(S);
+
+// Secret integer
+pub type SecretZZ = Secret<ZZ>;
+
+impl<S: Zeroize> Secret<S> {
+    pub fn new(value: S) -> Self {
+        Self(value)
+    }
+
+    // Read access to the value
+    pub fn expose(&self) -> &S {
+        &self.0
+    }
+
+    // Write access to the value, e.g. to update a key in place
+    pub fn expose_mut(&mut self) -> &mut S {
+        &mut self.0
+    }
+}
+
+impl<S: Zeroize> From<S> for Secret<S> {
+    fn from(value: S) -> Self {
+        Self(value)
+    }
+}
+
+impl<S: Zeroize> Deref for Secret<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.0
+    }
+}
+
+impl<S: Zeroize> Debug for Secret<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> ark_std::fmt::Result {
+        write!(f, "Secret(..)")
+    }
+}
+
+impl<S: Zeroize> Zeroize for Secret<S> {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<S: Zeroize> Drop for Secret<S> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<S: Zeroize> ZeroizeOnDrop for Secret<S> {}
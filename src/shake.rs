@@ -0,0 +1,79 @@
+// SHAKE256 extendable output function (FIPS 202), used to expand hash inputs into integers
+
+use ark_std::rand::{Error, RngCore};
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+
+// Sponge state. Absorb with update, then read any number of bytes with squeeze.
+// Updating after the first squeeze is not supported.
+#[derive(Clone)]
+pub struct Shake256 {
+    state: State,
+}
+
+#[derive(Clone)]
+enum State {
+    Absorbing(sha3::Shake256),
+    Squeezing(sha3::Shake256Reader),
+}
+
+impl Default for Shake256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for Shake256 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let squeezing = matches!(self.state, State::Squeezing(_));
+        f.debug_struct("Shake256").field("squeezing", &squeezing).finish_non_exhaustive()
+    }
+}
+
+impl Shake256 {
+    pub fn new() -> Self {
+        Self {
+            state: State::Absorbing(sha3::Shake256::default()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.state {
+            State::Absorbing(hasher) => hasher.update(data),
+            State::Squeezing(_) => panic!("SHAKE256 cannot absorb after squeezing"),
+        }
+    }
+
+    // Fills out with the next bytes of output
+    pub fn squeeze(&mut self, out: &mut [u8]) {
+        if let State::Absorbing(hasher) = &mut self.state {
+            self.state = State::Squeezing(std::mem::take(hasher).finalize_xof());
+        }
+        if let State::Squeezing(reader) = &mut self.state {
+            reader.read(out);
+        }
+    }
+}
+
+// The output stream as a deterministic rng, e.g. seeded with a domain and a discriminant
+impl RngCore for Shake256 {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.squeeze(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.squeeze(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.squeeze(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.squeeze(dest);
+        Ok(())
+    }
+}
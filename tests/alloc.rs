@@ -0,0 +1,86 @@
+// Counts GMP allocations to check that the in-place core stops allocating once its buffers have grown.
+// This replaces the process-wide GMP memory functions, so it lives in its own test binary.
+use std::{
+    ffi::c_void,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
+
+use ark_guo::{
+    class::{
+        config::{ClassConfig, TestClassConfig},
+        context::ClassGroupContext,
+        ClassGroup,
+    },
+    integer::ZZ,
+};
+use gmp_mpfr_sys::gmp;
+
+type CG = ClassGroup<TestClassConfig>;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static DEFAULTS: OnceLock<(gmp::allocate_function, gmp::reallocate_function, gmp::free_function)> = OnceLock::new();
+
+extern "C" fn counting_alloc(size: usize) -> *mut c_void {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    DEFAULTS.get().unwrap().0.unwrap()(size)
+}
+
+unsafe extern "C" fn counting_realloc(ptr: *mut c_void, old_size: usize, new_size: usize) -> *mut c_void {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    unsafe { DEFAULTS.get().unwrap().1.unwrap()(ptr, old_size, new_size) }
+}
+
+unsafe extern "C" fn counting_free(ptr: *mut c_void, size: usize) {
+    unsafe { DEFAULTS.get().unwrap().2.unwrap()(ptr, size) }
+}
+
+fn count_allocations() {
+    let (mut alloc, mut realloc, mut free) = (None, None, None);
+    unsafe { gmp::get_memory_functions(&mut alloc, &mut realloc, &mut free) };
+    DEFAULTS.set((alloc, realloc, free)).unwrap();
+    unsafe { gmp::set_memory_functions(Some(counting_alloc), Some(counting_realloc), Some(counting_free)) };
+}
+
+#[test]
+fn in_place_core_does_not_allocate() {
+    count_allocations();
+
+    let ctx = &mut ClassGroupContext::new();
+    let g = TestClassConfig::generator() * ZZ::from(0xdead_beef_u64);
+    let mut r = g.clone();
+    let mut t = CG::default();
+
+    // Warm up the buffers of ctx, r and t, then check that squaring and composing stop allocating
+    let mut step = |r: &mut CG, t: &mut CG| {
+        CG::nudupl_into(ctx, t, r);
+        CG::nucomp_into(ctx, r, t, &g);
+    };
+    for _ in 0..200 {
+        step(&mut r, &mut t);
+    }
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..200 {
+        step(&mut r, &mut t);
+    }
+    assert_eq!(ALLOCATIONS.load(Ordering::Relaxed), before);
+    assert!(before > 0);
+
+    // Same for the methods without a context, which run on the context of the thread
+    let step = |r: &mut CG, t: &mut CG| {
+        CG::nudupl(t, r);
+        CG::nucomp(r, t, &g);
+    };
+    for _ in 0..200 {
+        step(&mut r, &mut t);
+    }
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..200 {
+        step(&mut r, &mut t);
+    }
+    assert_eq!(ALLOCATIONS.load(Ordering::Relaxed), before);
+}
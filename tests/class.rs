@@ -0,0 +1,1346 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use ark_guo::{
+    class::{
+        config::{ClassConfig, FormConfig, TestClassConfig},
+        fixed_base::FixedBaseTable,
+        number::{class_number, ClassNumberError},
+        unreduced::UnreducedForm,
+        vec::ClassGroupVec,
+        sample::SamplingStrategy,
+        structure::{ClassGroupStructure, StructureError},
+        ClassGroup, ClassGroupError, PowStrategy,
+    },
+    integer::{
+        factor::factor,
+        hgcd::{partial_xgcd_with_base_bits, PartialXgcd},
+        ZZ,
+    },
+    msm::VariableBaseMSM,
+    AdditiveGroup, AdditiveGroupRef, Integer, IntegerRef,
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Valid};
+use ark_std::{rand::Rng, test_rng, UniformRand, Zero};
+
+// Unit struct implementing FormConfig with ZZ and ClassConfig with the given discriminant and NUCOMP bound, optionally
+// overriding further items of ClassConfig
+macro_rules! class_config {
+    ($name:ident, $disc:expr, $bound:expr $(, $($item:item)*)?) => {
+        #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, Hash)]
+        struct $name;
+
+        impl FormConfig for $name {
+            type Int = ZZ;
+        }
+
+        impl ClassConfig for $name {
+            $($($item)*)?
+
+            fn discriminant() -> Self::Int {
+                $disc
+            }
+
+            fn default_nucomp_bound() -> Self::Int {
+                $bound
+            }
+        }
+    };
+}
+
+// Small discriminant, D = 1 mod 4
+class_config!(SmallClassConfig, ZZ::from(-1000003i64), ZZ::from(22i64));
+
+// 128-bit discriminant, D = 1 mod 4
+class_config!(MediumClassConfig, -ZZ::from("286351342250401058177601183925669056055"), ZZ::from("2908772850"));
+
+// 98-bit discriminant, D = 0 mod 4
+class_config!(EvenClassConfig, -ZZ::from("292296457703694131230113527972"), ZZ::from("16441479"));
+
+// Prime forms of the first n primes p with (D / p) = 1
+fn prime_forms<T: ClassConfig<Int = ZZ>>(n: usize) -> Vec<ClassGroup<T>> {
+    (2u64..).filter_map(|p| ClassGroup::prime_form(ZZ::from(p)).ok()).take(n).collect()
+}
+
+fn random_element<T: ClassConfig<Int = ZZ>, R: Rng>(forms: &[ClassGroup<T>], rng: &mut R) -> ClassGroup<T> {
+    let mut r = ClassGroup::zero();
+    for f in forms {
+        let mut t = ClassGroup::default();
+        ClassGroup::compose_classical(&mut t, &r, &classical_pow(f, rng.gen_range(0u64..64)));
+        r = t;
+    }
+    r
+}
+
+fn classical_pow<T: ClassConfig<Int = ZZ>>(f: &ClassGroup<T>, n: u64) -> ClassGroup<T> {
+    let mut r = ClassGroup::zero();
+    for i in (0..64).rev() {
+        let mut t = ClassGroup::default();
+        ClassGroup::compose_classical(&mut t, &r, &r);
+        r = t;
+        if (n >> i) & 1 == 1 {
+            let mut t = ClassGroup::default();
+            ClassGroup::compose_classical(&mut t, &r, f);
+            r = t;
+        }
+    }
+    r
+}
+
+// D = -60 = 4 * -15, with the non-primitive form (2, 2, 8)
+class_config!(NonFundamentalClassConfig, ZZ::from(-60i64), ZZ::from(2i64));
+
+// TestClassConfig with the partial Euclid of NUCOMP and NUDUPL always going through half gcds
+class_config!(
+    HalfGcdClassConfig,
+    TestClassConfig::discriminant(),
+    TestClassConfig::default_nucomp_bound(),
+    fn half_gcd_threshold_bits() -> u32 {
+        0
+    }
+);
+
+fn check_against_classical<T: ClassConfig<Int = ZZ>>(trials: usize) {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<T>(6);
+
+    for _ in 0..trials {
+        let f = random_element(&forms, rng);
+        let g = random_element(&forms, rng);
+
+        let mut expected = ClassGroup::default();
+        ClassGroup::compose_classical(&mut expected, &f, &g);
+        let mut r = ClassGroup::default();
+        ClassGroup::nucomp(&mut r, &f, &g);
+        // Valid::check accepts exactly the reduced primitive forms of the discriminant of T
+        assert!(r.check().is_ok());
+        assert_eq!(r, expected);
+
+        ClassGroup::compose_classical(&mut expected, &f, &f);
+        ClassGroup::nudupl(&mut r, &f);
+        assert!(r.check().is_ok());
+        assert_eq!(r, expected);
+
+        let n: u64 = rng.gen_range(0..u64::MAX);
+        let expected = classical_pow(&f, n);
+        assert_eq!(f.clone() * ZZ::from(n), expected);
+        assert_eq!((-f.clone()) * -ZZ::from(n), expected);
+    }
+}
+
+#[test]
+fn nucomp_nudupl_nupow_match_classical_small() {
+    check_against_classical::<SmallClassConfig>(50);
+}
+
+#[test]
+fn nucomp_nudupl_nupow_match_classical_medium() {
+    check_against_classical::<MediumClassConfig>(50);
+}
+
+#[test]
+fn nucomp_nudupl_nupow_match_classical_even() {
+    check_against_classical::<EvenClassConfig>(50);
+}
+
+#[test]
+fn nucomp_nudupl_nupow_match_classical_test_config() {
+    check_against_classical::<TestClassConfig>(5);
+}
+
+fn check_nudupl<T: ClassConfig<Int = ZZ>>(trials: usize) {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<T>(6);
+
+    let mut r = ClassGroup::default();
+    ClassGroup::nudupl(&mut r, &ClassGroup::<T>::zero());
+    assert!(r.is_zero());
+
+    for _ in 0..trials {
+        // 2^k f by repeated NUDUPL
+        let f = random_element(&forms, rng);
+        let mut g = f.clone();
+        for k in 1..=10 {
+            let mut r = ClassGroup::default();
+            ClassGroup::nudupl(&mut r, &g);
+            assert!(r.check().is_ok());
+            assert_eq!(r, classical_pow(&f, 1 << k));
+            g = r;
+        }
+    }
+}
+
+#[test]
+fn nudupl_doubles() {
+    check_nudupl::<SmallClassConfig>(20);
+    check_nudupl::<EvenClassConfig>(20);
+    check_nudupl::<TestClassConfig>(2);
+
+    // The ambiguous form (3, 0, 5) has order 2, and (1, 0, 15) is the identity
+    let f = ClassGroup::<NonFundamentalClassConfig>::new(ZZ::from(3), ZZ::zero(), ZZ::from(5));
+    let mut r = ClassGroup::default();
+    ClassGroup::nudupl(&mut r, &f);
+    assert_eq!(r, ClassGroup::new(ZZ::from(1), ZZ::zero(), ZZ::from(15)));
+}
+
+fn check_group_laws<T: ClassConfig<Int = ZZ>>(trials: usize) {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<T>(6);
+    let zero = ClassGroup::<T>::zero();
+
+    for _ in 0..trials {
+        let f = random_element(&forms, rng);
+        let g = random_element(&forms, rng);
+        let h = random_element(&forms, rng);
+
+        assert_eq!(f.clone() + &zero, f);
+        assert!((f.clone() + -f.clone()).is_zero());
+        assert_eq!(f.clone() + &g, g.clone() + &f);
+        assert_eq!((f.clone() + &g) + &h, f.clone() + (g.clone() + &h));
+
+        // Equal first coefficients go through NUCOMP too
+        let mut r = ClassGroup::default();
+        ClassGroup::nucomp(&mut r, &f, &f);
+        let mut expected = ClassGroup::default();
+        ClassGroup::nudupl(&mut expected, &f);
+        assert_eq!(r, expected);
+
+        // Sub, SubAssign and Sum
+        assert_eq!((f.clone() - &g) + &g, f);
+        let mut d = f.clone();
+        d -= g.clone();
+        assert_eq!(d, f.clone() - g.clone());
+        let sum: ClassGroup<T> = [f.clone(), g.clone(), h.clone()].into_iter().sum();
+        assert_eq!(sum, f + g + h);
+    }
+}
+
+#[test]
+fn nucomp_satisfies_group_laws() {
+    check_group_laws::<SmallClassConfig>(20);
+    check_group_laws::<MediumClassConfig>(20);
+    check_group_laws::<EvenClassConfig>(20);
+    check_group_laws::<TestClassConfig>(2);
+}
+
+// D = -15 with the ambiguous form (2, 1, 2), a = c
+class_config!(AmbiguousEqualClassConfig, ZZ::from(-15i64), ZZ::from(1i64));
+
+// D = -39 with the ambiguous form (3, 3, 4), b = a
+class_config!(AmbiguousEdgeClassConfig, ZZ::from(-39i64), ZZ::from(1i64));
+
+fn check_negation_is_reduced<T: ClassConfig<Int = ZZ>>(f: ClassGroup<T>) {
+    let zero = ClassGroup::<T>::zero();
+    assert_eq!(-zero.clone(), zero);
+    assert!((-&zero).is_zero());
+
+    // Ambiguous forms are their own inverse, and the negation must stay canonical and valid
+    assert_eq!(-f.clone(), f);
+    assert_eq!(-&f, f);
+    for g in [f.clone(), zero] {
+        let neg = -g.clone();
+        assert!(neg.is_reduced());
+        let mut bytes = Vec::new();
+        neg.serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(ClassGroup::<T>::deserialize_compressed(&bytes[..]).unwrap(), neg);
+    }
+}
+
+#[test]
+fn negation_is_reduced() {
+    check_negation_is_reduced(ClassGroup::<AmbiguousEqualClassConfig>::try_new(2u64.into(), 1u64.into(), 2u64.into()).unwrap());
+    check_negation_is_reduced(ClassGroup::<AmbiguousEdgeClassConfig>::try_new(3u64.into(), 3u64.into(), 4u64.into()).unwrap());
+    assert_eq!(-ClassGroup::<SmallClassConfig>::zero(), ClassGroup::zero());
+    assert_eq!(-ClassGroup::<EvenClassConfig>::zero(), ClassGroup::zero());
+}
+
+#[test]
+fn nupow_matches_exponent_mod_class_number() {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<SmallClassConfig>(6);
+    let h = ZZ::from(class_number_naive::<SmallClassConfig>());
+
+    // Bit lengths across all window widths, with the runs of ones that make wNAF carry
+    let mut exponents = vec![ZZ::zero(), ZZ::from(1), ZZ::from(2), ZZ::from(3)];
+    for bits in [7u32, 8, 31, 64, 65, 200, 1000, 3000] {
+        let p = ZZ::from(2).pow(&bits);
+        exponents.extend([p.clone() - ZZ::from(1), p.clone(), p + ZZ::from(1)]);
+        exponents.push(random_bits(bits, rng));
+    }
+
+    for _ in 0..5 {
+        let f = random_element(&forms, rng);
+        for n in &exponents {
+            let k = n.rem_floor(&h).value.to_u64().unwrap();
+            assert_eq!(f.clone() * n, classical_pow(&f, k), "n = {n}");
+
+            let mut expected = -classical_pow(&f, k);
+            expected.reduce();
+            assert_eq!(f.clone() * &-n.clone(), expected, "n = -{n}");
+        }
+    }
+
+    // f^0 is the principal form of the discriminant of f, for D = 0 mod 4 as well
+    for f in prime_forms::<EvenClassConfig>(3) {
+        assert!((f * ZZ::zero()).is_zero());
+    }
+}
+
+fn check_nucube_and_double_base<T: ClassConfig<Int = ZZ>>(trials: usize) {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<T>(6);
+
+    for _ in 0..trials {
+        let f = random_element(&forms, rng);
+
+        let mut r = ClassGroup::default();
+        ClassGroup::nucube(&mut r, &f);
+        assert!(r.check().is_ok());
+        assert_eq!(r, classical_pow(&f, 3));
+        let mut u = ClassGroup::default();
+        ClassGroup::nucube_unreduced(&mut u, &f);
+        u.reduce();
+        assert_eq!(u, r);
+
+        let n: u64 = rng.gen_range(0..u64::MAX);
+        ClassGroup::nupow_with(&mut r, &f, &ZZ::from(n), PowStrategy::DoubleBase);
+        assert_eq!(r, classical_pow(&f, n));
+        ClassGroup::nupow_with(&mut r, &f, &-ZZ::from(n), PowStrategy::DoubleBase);
+        let mut expected = -classical_pow(&f, n);
+        expected.reduce();
+        assert_eq!(r, expected);
+    }
+}
+
+#[test]
+fn nucube_and_double_base_match_classical() {
+    check_nucube_and_double_base::<SmallClassConfig>(50);
+    check_nucube_and_double_base::<MediumClassConfig>(50);
+    check_nucube_and_double_base::<EvenClassConfig>(50);
+    check_nucube_and_double_base::<TestClassConfig>(5);
+    check_nucube_and_double_base::<HalfGcdClassConfig>(5);
+    // Ramified primes give forms with gcd(a, b) > 1
+    check_nucube_and_double_base::<TwoRankClassConfig>(50);
+}
+
+#[test]
+fn fixed_base_table_matches_nupow() {
+    let rng = &mut test_rng();
+    let g = MediumClassConfig::generator();
+
+    for window in [1, 3, 4] {
+        let table = FixedBaseTable::new(&g, 64, window).unwrap();
+        for _ in 0..20 {
+            let e = ZZ::from(rng.gen_range(0..u64::MAX));
+            assert_eq!(table.mul(&e).unwrap(), g.clone() * &e);
+            assert_eq!(table.mul(&-e.clone()).unwrap(), g.clone() * -e);
+        }
+        assert!(table.mul(&ZZ::zero()).unwrap().is_zero());
+
+        // 2^64 has 65 bits
+        let e = ZZ::from(1u128 << 64);
+        assert_eq!(table.mul(&e), Err(ClassGroupError::ExponentTooLarge));
+        assert_eq!(table.mul(&-e), Err(ClassGroupError::ExponentTooLarge));
+    }
+
+    // Round trip through serialization, e.g. for caching on disk
+    let table = FixedBaseTable::new(&g, 64, 4).unwrap();
+    let mut bytes = Vec::new();
+    table.serialize_compressed(&mut bytes).unwrap();
+    let read = FixedBaseTable::<MediumClassConfig>::deserialize_compressed(&bytes[..]).unwrap();
+    assert_eq!(read, table);
+}
+
+#[test]
+fn fixed_base_table_rejects_wrong_dimensions() {
+    let g = MediumClassConfig::generator();
+    assert_eq!(FixedBaseTable::new(&g, 64, 0), Err(ClassGroupError::InvalidWindow));
+    assert_eq!(FixedBaseTable::new(&g, 64, 32), Err(ClassGroupError::InvalidWindow));
+    assert_eq!(FixedBaseTable::new(&g, u32::MAX, 1), Err(ClassGroupError::InvalidWindow));
+
+    // The fields are private, so tables of the wrong shape can only come from bytes, written here
+    // in the layout of the derived CanonicalSerialize
+    let table = FixedBaseTable::new(&g, 64, 4).unwrap();
+    let reads = |window: u32, max_bits: u32, rows: Vec<Vec<ClassGroup<MediumClassConfig>>>| {
+        let mut bytes = Vec::new();
+        (window, max_bits, rows).serialize_compressed(&mut bytes).unwrap();
+        FixedBaseTable::<MediumClassConfig>::deserialize_compressed(&bytes[..]).is_ok()
+    };
+    assert!(reads(table.window(), table.max_bits(), table.table().to_vec()));
+
+    // max_bits claims more windows than the table has
+    assert!(!reads(4, 128, table.table().to_vec()));
+
+    // Rows are shorter than 2^(window - 1)
+    assert!(!reads(5, 64, table.table().to_vec()));
+    let mut rows = table.table().to_vec();
+    rows[3].pop();
+    assert!(!reads(4, 64, rows));
+
+    assert!(!reads(0, 64, table.table().to_vec()));
+
+    // The number of windows overflows
+    assert!(!reads(1, u32::MAX, table.table().to_vec()));
+}
+
+#[test]
+fn msm_matches_naive() {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<MediumClassConfig>(6);
+
+    for n in [0, 1, 5, 15, 16, 40] {
+        let bases: Vec<ClassGroup<MediumClassConfig>> = (0..n).map(|_| random_element(&forms, rng)).collect();
+        let scalars: Vec<ZZ> = (0..n)
+            .map(|_| ZZ::from(rng.gen_range(i64::MIN..i64::MAX)))
+            .collect();
+
+        let expected: ClassGroup<MediumClassConfig> = bases
+            .iter()
+            .zip(&scalars)
+            .map(|(b, s)| b.clone() * s)
+            .sum();
+        assert_eq!(ClassGroup::msm(&bases, &scalars), expected);
+        assert_eq!(ClassGroup::msm_straus(&bases, &scalars), expected);
+        assert_eq!(ClassGroup::msm_pippenger(&bases, &scalars), expected);
+    }
+}
+
+fn gcd(x: i64, y: i64) -> i64 {
+    if y == 0 { x.abs() } else { gcd(y, x % y) }
+}
+
+// Number of reduced primitive forms, i.e. the class number, for a small discriminant
+fn class_number_naive<T: ClassConfig<Int = ZZ>>() -> u64 {
+    class_number_count(T::discriminant().into())
+}
+
+fn class_number_count(disc: i64) -> u64 {
+    let mut h = 0;
+    let mut a = 1i64;
+    while 3 * a * a <= -disc {
+        for b in (-a + 1)..=a {
+            if (b * b - disc) % (4 * a) != 0 {
+                continue;
+            }
+            let c = (b * b - disc) / (4 * a);
+            if c >= a && !(c == a && b < 0) && gcd(gcd(a, b), c) == 1 {
+                h += 1;
+            }
+        }
+        a += 1;
+    }
+    h
+}
+
+#[test]
+fn pow_secret_matches_nupow() {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<SmallClassConfig>(6);
+    let h = ZZ::from(class_number_naive::<SmallClassConfig>());
+
+    for _ in 0..20 {
+        let f = random_element(&forms, rng);
+        let n = ZZ::from(rng.gen_range(i64::MIN..i64::MAX));
+
+        let expected = f.clone() * &n;
+        let mut r = ClassGroup::default();
+        ClassGroup::pow_secret(&mut r, &f, &n, 64).unwrap();
+        assert_eq!(r, expected);
+        ClassGroup::pow_secret_blinded(&mut r, &f, &n, 64, &h, rng).unwrap();
+        assert_eq!(r, expected);
+    }
+
+    // 2^64 has 65 bits
+    let f = forms[0].clone();
+    let n = ZZ::from(1u128 << 64);
+    let mut r = ClassGroup::default();
+    assert_eq!(ClassGroup::pow_secret(&mut r, &f, &n, 64), Err(ClassGroupError::ExponentTooLarge));
+    assert_eq!(ClassGroup::pow_secret(&mut r, &f, &-n.clone(), 64), Err(ClassGroupError::ExponentTooLarge));
+    assert_eq!(ClassGroup::pow_secret_blinded(&mut r, &f, &n, 64, &h, rng), Err(ClassGroupError::ExponentTooLarge));
+}
+
+fn check_unreduced_chain<T: ClassConfig<Int = ZZ>>(trials: usize) {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<T>(6);
+
+    for _ in 0..trials {
+        let chain: Vec<ClassGroup<T>> = (0..20).map(|_| random_element(&forms, rng)).collect();
+        let expected: ClassGroup<T> = chain.iter().cloned().sum();
+        assert_eq!(UnreducedForm::product(chain.clone()), expected);
+
+        // Interleave doublings, reducing only at the end
+        let mut acc = UnreducedForm::from(ClassGroup::zero());
+        let mut expected = ClassGroup::zero();
+        for f in &chain {
+            acc = acc.double();
+            acc += &UnreducedForm::from(f.clone());
+            expected = expected.clone() + &expected + f;
+        }
+
+        // Equal to the reduced form of its class, and hashed alike
+        let reduced = UnreducedForm::from(expected.clone());
+        assert_eq!(acc, reduced);
+        let hash = |f: &UnreducedForm<T>| {
+            let mut h = DefaultHasher::new();
+            f.hash(&mut h);
+            h.finish()
+        };
+        assert_eq!(hash(&acc), hash(&reduced));
+        assert_ne!(acc, UnreducedForm::from(expected.clone() + &forms[0]));
+
+        let r = acc.reduce();
+        assert!(r.check().is_ok());
+        assert_eq!(r, expected);
+    }
+}
+
+#[test]
+fn unreduced_chain_matches_reduced() {
+    check_unreduced_chain::<SmallClassConfig>(20);
+    check_unreduced_chain::<MediumClassConfig>(20);
+    check_unreduced_chain::<EvenClassConfig>(20);
+    check_unreduced_chain::<TestClassConfig>(2);
+}
+
+// Runs a chain on UnreducedForm next to the raw unreduced NUCOMP and NUDUPL of its coefficients, which must be
+// kept as they are below the default bound and reduced above it, and checks that both happen
+fn check_unreduced_bound<T: ClassConfig<Int = ZZ>>(steps: usize) {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<T>(6);
+    let bound = T::unreduced_bound_bits();
+    let exceeds = |f: &ClassGroup<T>| f.a.significant_bits() > bound || f.b.significant_bits() > bound;
+
+    let elements: Vec<ClassGroup<T>> = (0..8).map(|_| random_element(&forms, rng)).collect();
+    let mut acc = UnreducedForm::from(elements[0].clone());
+    let (mut lazy, mut fired) = (false, false);
+    for i in 0..steps {
+        let (a, b, c) = acc.coefficients();
+        let f = ClassGroup::new_unchecked(a.clone(), b.clone(), c.clone());
+        let mut raw = ClassGroup::default();
+        if i % 3 == 0 {
+            ClassGroup::nudupl_unreduced(&mut raw, &f);
+            acc = acc.double();
+        } else {
+            let g = &elements[i % elements.len()];
+            ClassGroup::nucomp_unreduced(&mut raw, &f, g);
+            acc += &UnreducedForm::from(g.clone());
+        }
+
+        let (a, b, c) = acc.coefficients();
+        let f = ClassGroup::<T>::new_unchecked(a.clone(), b.clone(), c.clone());
+        assert!(!acc.exceeds_bound());
+        if exceeds(&raw) {
+            assert!(f.is_reduced());
+            fired = true;
+        } else {
+            assert_eq!(f, raw);
+            lazy |= !raw.is_reduced();
+        }
+    }
+    assert!(lazy && fired);
+}
+
+#[test]
+fn unreduced_bound_triggers_reduction() {
+    check_unreduced_bound::<MediumClassConfig>(500);
+    check_unreduced_bound::<TestClassConfig>(500);
+}
+
+// Equivalent form f(x + k y, y) swapped to (c, -b, a), repeated with random k, so a and c blow up
+fn scramble<T: ClassConfig<Int = ZZ>, R: Rng>(f: &ClassGroup<T>, rounds: usize, rng: &mut R) -> ClassGroup<T> {
+    let (mut a, mut b, mut c) = (f.a.clone(), f.b.clone(), f.c.clone());
+    for _ in 0..rounds {
+        let k = ZZ::from(rng.gen_range(-1_000_000i64..1_000_000));
+        c = a.clone() * &k * &k + b.clone() * &k + &c;
+        b += ZZ::from(2) * a.clone() * &k;
+        (a, b, c) = (c, -b, a);
+    }
+    ClassGroup::new(a, b, c)
+}
+
+fn check_reduce_scrambled<T: ClassConfig<Int = ZZ>>(trials: usize) {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<T>(6);
+
+    for _ in 0..trials {
+        let f = random_element(&forms, rng);
+        for rounds in [1, 2, 5, 20, 100] {
+            let mut g = scramble(&f, rounds, rng);
+            g.reduce();
+            assert_eq!(g, f);
+        }
+    }
+}
+
+#[test]
+fn reduce_recovers_scrambled_forms() {
+    check_reduce_scrambled::<SmallClassConfig>(20);
+    check_reduce_scrambled::<MediumClassConfig>(20);
+    check_reduce_scrambled::<EvenClassConfig>(20);
+    check_reduce_scrambled::<TestClassConfig>(5);
+}
+
+fn random_bits<R: Rng>(bits: u32, rng: &mut R) -> ZZ {
+    let mut x = ZZ::zero();
+    for _ in 0..bits.div_ceil(64) {
+        x = x * ZZ::from(1u128 << 64) + ZZ::from(rng.gen_range(0..u64::MAX));
+    }
+    x >> ((64 - bits % 64) % 64)
+}
+
+fn partial_xgcd_naive(a: &ZZ, b: &ZZ, bound: &ZZ) -> PartialXgcd<ZZ> {
+    let mut p = PartialXgcd {
+        r0: a.clone(),
+        r1: b.clone(),
+        s0: ZZ::from(1),
+        t0: ZZ::zero(),
+        s1: ZZ::zero(),
+        t1: ZZ::from(1),
+        steps: 0,
+    };
+    while p.r0 > *bound && !p.r1.is_zero() {
+        let mut q = p.r0.clone();
+        let mut r = p.r1.clone();
+        q.div_rem_floor_mut(&mut r);
+        let s = p.s0.clone() - q.clone() * &p.s1;
+        let t = p.t0.clone() - q * &p.t1;
+        p = PartialXgcd {
+            r0: p.r1,
+            r1: r,
+            s0: p.s1,
+            t0: p.t1,
+            s1: s,
+            t1: t,
+            steps: p.steps + 1,
+        };
+    }
+    p
+}
+
+#[test]
+fn partial_xgcd_matches_naive() {
+    let rng = &mut test_rng();
+
+    for bits in [100, 300, 1000, 3000, 10000, 20000] {
+        for _ in 0..10 {
+            let a = random_bits(bits, rng) + ZZ::from(2);
+            let b = random_bits(bits, rng).rem_floor(&a);
+            let bound = random_bits(rng.gen_range(0..bits), rng);
+
+            let p = a.partial_xgcd(&b, &bound);
+            assert_eq!(p, partial_xgcd_naive(&a, &b, &bound));
+            // Deeper recursion, and Lehmer steps only
+            assert_eq!(partial_xgcd_with_base_bits(&a, &b, &bound, 128), p);
+            assert_eq!(partial_xgcd_with_base_bits(&a, &b, &bound, u32::MAX), p);
+            assert_eq!(p.s0.clone() * &a + p.t0.clone() * &b, p.r0);
+            assert_eq!(p.s1.clone() * &a + p.t1.clone() * &b, p.r1);
+        }
+    }
+
+    // Consecutive Fibonacci numbers, all quotients 1
+    let (mut a, mut b) = (ZZ::from(1), ZZ::from(1));
+    for _ in 0..5000 {
+        (a, b) = (a.clone() + &b, a);
+    }
+    for bound in [ZZ::zero(), ZZ::from(1000), b.clone() >> 1000] {
+        assert_eq!(a.partial_xgcd(&b, &bound), partial_xgcd_naive(&a, &b, &bound));
+    }
+
+    // Quotients of hundreds of bits, wider than the leading words of the Lehmer steps
+    let mut a = ZZ::from(1);
+    let mut b = ZZ::from(1);
+    for _ in 0..20 {
+        let q = random_bits(300, rng);
+        (a, b) = (q * &a + &b, a);
+    }
+    for bound in [ZZ::zero(), b.clone() >> 3000] {
+        assert_eq!(a.partial_xgcd(&b, &bound), partial_xgcd_naive(&a, &b, &bound));
+    }
+}
+
+#[test]
+fn half_gcd_partial_euclid_matches_classical() {
+    check_against_classical::<HalfGcdClassConfig>(5);
+    check_unreduced_chain::<HalfGcdClassConfig>(2);
+}
+
+fn check_constructors_and_predicates<T: ClassConfig<Int = ZZ>>() {
+    let rng = &mut test_rng();
+
+    assert!(ClassGroup::<T>::zero().is_identity());
+    assert!(ClassGroup::<T>::zero().is_reduced());
+
+    for f in prime_forms::<T>(10) {
+        assert_eq!(ClassGroup::try_new(f.a.clone(), f.b.clone(), f.c.clone()), Ok(f.clone()));
+        assert_eq!(ClassGroup::from_ab(f.a.clone(), f.b.clone()), Ok(f.clone()));
+        assert!(f.is_normal() && f.is_reduced() && f.is_primitive());
+        assert!(!f.is_identity());
+
+        let g = scramble(&f, 3, rng);
+        assert!(!g.is_reduced() && g.is_primitive());
+        assert_eq!(ClassGroup::try_new(g.a.clone(), g.b.clone(), g.c.clone()), Ok(g));
+
+        assert_eq!(
+            ClassGroup::<T>::try_new(-f.a.clone(), f.b.clone(), -f.c.clone()),
+            Err(ClassGroupError::NonPositive)
+        );
+        assert_eq!(
+            ClassGroup::<T>::try_new(f.a.clone(), f.b.clone(), f.c.clone() + ZZ::from(1)),
+            Err(ClassGroupError::WrongDiscriminant)
+        );
+        assert_eq!(
+            ClassGroup::<T>::from_ab(f.a.clone(), f.b.clone() + ZZ::from(1)),
+            Err(ClassGroupError::NotDivisible)
+        );
+        assert_eq!(ClassGroup::<T>::from_ab(ZZ::zero(), f.b.clone()), Err(ClassGroupError::NonPositive));
+    }
+}
+
+#[test]
+fn constructors_and_predicates() {
+    check_constructors_and_predicates::<SmallClassConfig>();
+    check_constructors_and_predicates::<EvenClassConfig>();
+    check_constructors_and_predicates::<TestClassConfig>();
+
+    let f = ClassGroup::<NonFundamentalClassConfig>::new(ZZ::from(2), ZZ::from(2), ZZ::from(8));
+    assert!(f.is_reduced() && !f.is_primitive());
+    assert_eq!(
+        ClassGroup::<NonFundamentalClassConfig>::try_new(f.a.clone(), f.b.clone(), f.c.clone()),
+        Err(ClassGroupError::NotPrimitive)
+    );
+    assert_eq!(
+        ClassGroup::<NonFundamentalClassConfig>::from_ab(ZZ::from(2), ZZ::from(2)),
+        Err(ClassGroupError::NotPrimitive)
+    );
+}
+
+fn serialized<T: ClassConfig<Int = ZZ>>(a: ZZ, b: ZZ, c: ZZ) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    ClassGroup::<T>::new_unchecked(a, b, c).serialize_compressed(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn deserialization_rejects_invalid_forms() {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<MediumClassConfig>(10);
+
+    for f in &forms {
+        let bytes = serialized::<MediumClassConfig>(f.a.clone(), f.b.clone(), f.c.clone());
+        assert_eq!(&ClassGroup::deserialize_compressed(&bytes[..]).unwrap(), f);
+
+        // Wrong discriminant, not positive, not reduced
+        let garbage = [
+            serialized::<MediumClassConfig>(f.a.clone(), f.b.clone(), f.c.clone() + ZZ::from(1)),
+            serialized::<MediumClassConfig>(-f.a.clone(), f.b.clone(), -f.c.clone()),
+            serialized::<MediumClassConfig>(f.c.clone(), -f.b.clone(), f.a.clone()),
+        ];
+        for bytes in &garbage {
+            assert!(ClassGroup::<MediumClassConfig>::deserialize_compressed(&bytes[..]).is_err());
+            assert!(ClassGroup::<MediumClassConfig>::deserialize_compressed_unchecked(&bytes[..]).is_ok());
+        }
+
+        let g = scramble(f, 3, rng);
+        let bytes = serialized::<MediumClassConfig>(g.a, g.b, g.c);
+        assert!(ClassGroup::<MediumClassConfig>::deserialize_compressed(&bytes[..]).is_err());
+    }
+
+    // A form of another group
+    let f = &SmallClassConfig::generator();
+    let bytes = serialized::<SmallClassConfig>(f.a.clone(), f.b.clone(), f.c.clone());
+    assert!(ClassGroup::<MediumClassConfig>::deserialize_compressed(&bytes[..]).is_err());
+
+    // Not primitive
+    let bytes = serialized::<NonFundamentalClassConfig>(ZZ::from(2), ZZ::from(2), ZZ::from(8));
+    assert!(ClassGroup::<NonFundamentalClassConfig>::deserialize_compressed(&bytes[..]).is_err());
+
+    // Vectors go through batch_check
+    let mut bytes = Vec::new();
+    forms.serialize_compressed(&mut bytes).unwrap();
+    assert_eq!(Vec::<ClassGroup<MediumClassConfig>>::deserialize_compressed(&bytes[..]).unwrap(), forms);
+
+    let mut bad = forms.clone();
+    bad[5].c += ZZ::from(1);
+    let mut bytes = Vec::new();
+    bad.serialize_compressed(&mut bytes).unwrap();
+    assert!(Vec::<ClassGroup<MediumClassConfig>>::deserialize_compressed(&bytes[..]).is_err());
+    assert!(Vec::<ClassGroup<MediumClassConfig>>::deserialize_compressed_unchecked(&bytes[..]).is_ok());
+}
+
+// -(x + y) * n + y - x, only on references
+fn combine_by_ref<G: AdditiveGroup>(x: &G, y: &G, n: &G::Scalar) -> G
+where
+    for<'a> &'a G: AdditiveGroupRef<'a, G>,
+{
+    let s = x + y;
+    let t = &-&s * n;
+    &(&t + y) - x
+}
+
+fn combine_by_value<G: AdditiveGroup>(x: &G, y: &G, n: &G::Scalar) -> G {
+    -(x.clone() + y.clone()) * n.clone() + y.clone() - x.clone()
+}
+
+// x^2 - x * y + y, only on references
+fn poly_by_ref<I: Integer>(x: &I, y: &I) -> I
+where
+    for<'a> &'a I: IntegerRef<'a, I>,
+{
+    &(&(x * x) - &(x * y)) + y
+}
+
+#[test]
+fn reference_ops_match_owned_ops() {
+    let rng = &mut test_rng();
+
+    for _ in 0..20 {
+        let x = ZZ::from(rng.gen_range(-1_000_000_000i64..1_000_000_000));
+        let y = ZZ::from(rng.gen_range(-1_000_000_000i64..1_000_000_000));
+        let n = ZZ::from(rng.gen_range(-1000i64..1000));
+        assert_eq!(combine_by_ref(&x, &y, &n), combine_by_value(&x, &y, &n));
+        assert_eq!(poly_by_ref(&x, &y), x.clone() * &x - x.clone() * &y + &y);
+        assert_eq!(-&x, -x.clone());
+    }
+
+    let forms = prime_forms::<MediumClassConfig>(6);
+    for _ in 0..20 {
+        let f = random_element(&forms, rng);
+        let g = random_element(&forms, rng);
+        let n = ZZ::from(rng.gen_range(-1_000_000i64..1_000_000));
+        assert_eq!(combine_by_ref(&f, &g, &n), combine_by_value(&f, &g, &n));
+        assert_eq!(&f + &g, f.clone() + &g);
+        assert_eq!(&f - &g, f.clone() - &g);
+        assert_eq!(&f * &n, f.clone() * &n);
+        assert_eq!(-&f, -f.clone());
+        assert!((-&f).is_reduced());
+    }
+}
+
+#[test]
+fn batch_ops_match_single_ops() {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<MediumClassConfig>(6);
+    let n = 37;
+
+    let a: Vec<_> = (0..n).map(|_| random_element(&forms, rng)).collect();
+    let b: Vec<_> = (0..n).map(|_| random_element(&forms, rng)).collect();
+    let scalars: Vec<_> = (0..n).map(|_| ZZ::from(rng.gen_range(-1_000_000i64..1_000_000))).collect();
+
+    let sums: Vec<_> = a.iter().zip(&b).map(|(x, y)| x + y).collect();
+    let doubles: Vec<_> = a.iter().map(|x| x.double()).collect();
+    let muls: Vec<_> = a.iter().zip(&scalars).map(|(x, s)| x * s).collect();
+    let total = a.iter().fold(ClassGroup::zero(), |acc, x| acc + x);
+
+    assert_eq!(ClassGroup::batch_add(&a, &b), sums);
+    assert_eq!(ClassGroup::batch_double(&a), doubles);
+    assert_eq!(ClassGroup::batch_scalar_mul(&a, &scalars), muls);
+    assert_eq!(ClassGroup::sum_tree(&a), total);
+    assert_eq!(ClassGroup::sum_tree(&a[..1]), a[0]);
+    assert!(ClassGroup::<MediumClassConfig>::sum_tree(&[]).is_zero());
+
+    let mut scrambled: Vec<_> = a.iter().map(|f| scramble(f, 5, rng)).collect();
+    ClassGroup::batch_reduce(&mut scrambled);
+    assert_eq!(scrambled, a);
+
+    // Default impls
+    let x: Vec<_> = (0..n).map(|_| ZZ::from(rng.gen_range(-1_000_000i64..1_000_000))).collect();
+    let y: Vec<_> = x.iter().map(|v| v.clone() * v).collect();
+    assert_eq!(ZZ::batch_add(&x, &y), x.iter().zip(&y).map(|(u, v)| u + v).collect::<Vec<_>>());
+    assert_eq!(ZZ::batch_double(&x), x.iter().map(|u| u + u).collect::<Vec<_>>());
+    assert_eq!(ZZ::batch_scalar_mul(&x, &y), x.iter().zip(&y).map(|(u, v)| u * v).collect::<Vec<_>>());
+    assert_eq!(ZZ::sum_tree(&x), x.iter().sum::<ZZ>());
+
+    // Structure of arrays
+    let va = ClassGroupVec::from(a.clone());
+    let vb: ClassGroupVec<_> = b.iter().cloned().collect();
+    assert_eq!(va.len(), n);
+    assert_eq!(va.get(3), a[3]);
+    assert_eq!(va.iter().collect::<Vec<_>>(), a);
+    assert_eq!(Vec::from(va.add(&vb)), sums);
+    assert_eq!(Vec::from(va.double()), doubles);
+    assert_eq!(Vec::from(va.scalar_mul(&scalars)), muls);
+    assert_eq!(va.sum(), total);
+    assert!(ClassGroupVec::<MediumClassConfig>::new().sum().is_zero());
+
+    let mut vs = ClassGroupVec::from(a.iter().map(|f| scramble(f, 5, rng)).collect::<Vec<_>>());
+    vs.reduce();
+    assert_eq!(vs, va);
+}
+
+#[test]
+fn class_group_vec_serialization() {
+    let rng = &mut test_rng();
+    let forms = prime_forms::<MediumClassConfig>(6);
+    let v: ClassGroupVec<MediumClassConfig> = (0..20).map(|_| random_element(&forms, rng)).collect();
+
+    let mut bytes = Vec::new();
+    v.serialize_compressed(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), v.compressed_size());
+    assert_eq!(ClassGroupVec::deserialize_compressed(&bytes[..]).unwrap(), v);
+
+    let empty = ClassGroupVec::<MediumClassConfig>::new();
+    let mut bytes = Vec::new();
+    empty.serialize_compressed(&mut bytes).unwrap();
+    assert!(ClassGroupVec::<MediumClassConfig>::deserialize_compressed(&bytes[..]).unwrap().is_empty());
+
+    // One form not reduced
+    let mut bad = v.clone();
+    std::mem::swap(&mut bad.a[7], &mut bad.c[7]);
+    let mut bytes = Vec::new();
+    bad.serialize_compressed(&mut bytes).unwrap();
+    assert!(ClassGroupVec::<MediumClassConfig>::deserialize_compressed(&bytes[..]).is_err());
+    assert_eq!(ClassGroupVec::<MediumClassConfig>::deserialize_compressed_unchecked(&bytes[..]).unwrap(), bad);
+
+    // Truncated
+    assert!(ClassGroupVec::<MediumClassConfig>::deserialize_compressed(&bytes[..bytes.len() - 1]).is_err());
+}
+
+fn check_sampling<T: ClassConfig<Int = ZZ>>() {
+    for strategy in [SamplingStrategy::PrimeProduct, SamplingStrategy::GeneratorExponent] {
+        let a: Vec<ClassGroup<T>> = (0..10).map(|_| ClassGroup::sample_with(&mut test_rng(), strategy)).collect();
+        let b: Vec<ClassGroup<T>> = (0..10).map(|_| ClassGroup::sample_with(&mut test_rng(), strategy)).collect();
+        assert_eq!(a, b);
+
+        let rng = &mut test_rng();
+        let samples: Vec<ClassGroup<T>> = (0..10).map(|_| ClassGroup::sample_with(rng, strategy)).collect();
+        for f in &samples {
+            assert!(f.check().is_ok());
+        }
+        assert!(samples.windows(2).any(|w| w[0] != w[1]));
+    }
+
+    let rng = &mut test_rng();
+    let f = ClassGroup::<T>::rand(rng);
+    assert!(f.check().is_ok());
+}
+
+#[test]
+fn sampling_is_reproducible_and_valid() {
+    check_sampling::<SmallClassConfig>();
+    check_sampling::<EvenClassConfig>();
+    check_sampling::<TestClassConfig>();
+}
+
+#[test]
+fn sampling_covers_small_class_group() {
+    let rng = &mut test_rng();
+    let h = class_number_naive::<SmallClassConfig>() as usize;
+    let n = 40 * h;
+
+    let mut counts = std::collections::HashMap::new();
+    for _ in 0..n {
+        *counts.entry(ClassGroup::<SmallClassConfig>::rand(rng)).or_insert(0usize) += 1;
+    }
+
+    // Every class shows up, none much more often than the expected 40 times
+    assert_eq!(counts.len(), h);
+    assert!(counts.values().all(|&k| (10..=90).contains(&k)));
+}
+
+fn check_hash_to_group<T: ClassConfig<Int = ZZ>>() {
+    for msg in [&b""[..], b"message", &[0xff; 300]] {
+        let f = ClassGroup::<T>::hash_to_group(b"test", msg);
+        assert!(f.check().is_ok());
+        assert_eq!(f, ClassGroup::hash_to_group(b"test", msg));
+    }
+}
+
+#[test]
+fn hash_to_group_is_deterministic_and_separated() {
+    check_hash_to_group::<SmallClassConfig>();
+    check_hash_to_group::<EvenClassConfig>();
+    check_hash_to_group::<NonFundamentalClassConfig>();
+    check_hash_to_group::<TestClassConfig>();
+
+    let f = ClassGroup::<TestClassConfig>::hash_to_group(b"test", b"message");
+    assert!(!f.is_identity());
+    assert_ne!(f, ClassGroup::hash_to_group(b"test", b"message2"));
+    assert_ne!(f, ClassGroup::hash_to_group(b"test2", b"message"));
+    // Length prefixes keep the boundary between domain and message
+    assert_ne!(f, ClassGroup::hash_to_group(b"testm", b"essage"));
+}
+
+#[test]
+fn hash_to_group_test_vectors() {
+    // The hashed prime is below sqrt(|D|) / 2, so the prime form is already reduced.
+    // a also comes out of Python's hashlib.shake_256 over the same length-prefixed domain, sign byte and
+    // big-endian |D|, and message
+    let vectors: [(&[u8], &str, &str); 2] = [
+        (
+            b"",
+            "97373931760097129761145858284266167005628946854289024320728697652300991815511",
+            "53160790720479030046392820558309519615656158413305492165155751277686787449585",
+        ),
+        (
+            b"abc",
+            "66613602595702228091589591166781725562061076465823624611368681346400531150953",
+            "3622783972614380917587196156052115460627085842146916395403361289490160794415",
+        ),
+    ];
+
+    for (msg, a, b) in vectors {
+        let f = ClassGroup::<TestClassConfig>::hash_to_group(b"ark-guo test", msg);
+        assert_eq!(f, ClassGroup::from_ab(ZZ::from(a), ZZ::from(b)).unwrap());
+    }
+}
+
+#[test]
+fn sqrt_mod_prime_matches_squares() {
+    // p = 3 mod 4, p = 5 mod 8 and p = 1 mod 2^k with large k go through different branches
+    let primes = ["1000003", "1000037", "998244353", "340282366920938463463374607431768211297"];
+    let rng = &mut test_rng();
+    for p in primes {
+        let p = ZZ::from(p);
+        assert!(p.is_probably_prime(30));
+        for _ in 0..20 {
+            let x = ZZ::from(rng.gen_range(0..u64::MAX)).rem_floor(&p);
+            let n = (x.clone() * &x - &p).rem_floor(&p);
+            let r = n.sqrt_mod_prime(&p).unwrap();
+            assert_eq!((r.clone() * &r).rem_floor(&p), n);
+
+            if n.kronecker(&p) == -1 {
+                assert!(n.sqrt_mod_prime(&p).is_none());
+            }
+            let m = x - ZZ::from(1u64);
+            if m.kronecker(&p) == -1 {
+                assert!(m.sqrt_mod_prime(&p).is_none());
+            }
+        }
+        assert_eq!(p.sqrt_mod_prime(&p), Some(ZZ::zero()));
+    }
+}
+
+// Reduced form (p, b, c) of an odd prime p not dividing D, by brute force on b
+fn prime_form_brute_force<T: ClassConfig<Int = ZZ>>(p: u64) -> Option<ClassGroup<T>> {
+    let disc = T::discriminant();
+    let four_p = ZZ::from(4 * p);
+    if disc.is_divisible(&ZZ::from(p)) {
+        return None;
+    }
+
+    (0..2 * p).map(ZZ::from).find_map(|b| {
+        let mut c = b.clone() * &b - &disc;
+        c.is_divisible(&four_p).then(|| {
+            c.div_exact(&four_p);
+            let mut f = ClassGroup::new(ZZ::from(p), b, c);
+            f.reduce();
+            f
+        })
+    })
+}
+
+fn check_prime_form<T: ClassConfig<Int = ZZ>>() {
+    let disc = T::discriminant();
+    for p in 0..1000u64 {
+        let r = ClassGroup::<T>::prime_form(ZZ::from(p));
+        let is_prime = p >= 2 && ZZ::from(p).is_probably_prime(30);
+        match r {
+            Ok(f) => {
+                assert!(is_prime);
+                assert!(f.check().is_ok());
+                if p > 2 {
+                    assert_eq!(Some(f), prime_form_brute_force(p));
+                }
+            }
+            Err(ClassGroupError::NotSplit) => {
+                assert!(disc.kronecker(&ZZ::from(p)) != 1);
+                if p > 2 && is_prime {
+                    assert_eq!(prime_form_brute_force::<T>(p), None);
+                }
+            }
+            Err(ClassGroupError::NotPrime) => assert!(!is_prime),
+            Err(e) => panic!("unexpected error {e}"),
+        }
+    }
+}
+
+#[test]
+fn prime_form_matches_brute_force() {
+    check_prime_form::<SmallClassConfig>();
+    check_prime_form::<MediumClassConfig>();
+    check_prime_form::<EvenClassConfig>();
+
+    // D = 1 mod 8 for MediumClassConfig, so 2 splits with b = 1
+    let f = ClassGroup::<MediumClassConfig>::prime_form(ZZ::from(2u64)).unwrap();
+    assert_eq!(f, ClassGroup::from_ab(ZZ::from(2u64), ZZ::from(1u64)).unwrap());
+    assert_eq!(ClassGroup::<SmallClassConfig>::prime_form(ZZ::from(2u64)), Err(ClassGroupError::NotSplit));
+    assert_eq!(ClassGroup::<SmallClassConfig>::prime_form(ZZ::from(-3i64)), Err(ClassGroupError::NotPrime));
+    assert_eq!(ClassGroup::<NonFundamentalClassConfig>::prime_form(ZZ::from(3u64)), Err(ClassGroupError::NotSplit));
+
+    // Large prime for the 2048-bit discriminant, where the form needs reducing
+    let mut p = ZZ::from(2u64).pow(&1500) + ZZ::from(1u64);
+    while ClassGroup::<TestClassConfig>::prime_form(p.clone()).is_err() {
+        p += &ZZ::from(2u64);
+    }
+    let f = ClassGroup::<TestClassConfig>::prime_form(p).unwrap();
+    assert!(f.check().is_ok());
+    assert!(!f.is_identity());
+}
+
+// TestClassConfig with the generator overridden to the prime form of 7
+class_config!(
+    FixedGeneratorClassConfig,
+    TestClassConfig::discriminant(),
+    TestClassConfig::default_nucomp_bound(),
+    const GENERATOR_PRIME: Option<u64> = Some(7);
+);
+
+#[test]
+fn generator_test_vectors() {
+    // Reduced (a, b, c) of the generator, the smallest split prime unless GENERATOR_PRIME is set.
+    // c = (b^2 - D) / 4a can be checked by hand against the discriminants of the configs.
+    fn check<T: ClassConfig<Int = ZZ>>(a: u64, b: i64, c: &str) {
+        let g = T::generator();
+        assert_eq!((g.a.clone(), g.b.clone(), g.c.clone()), (ZZ::from(a), ZZ::from(b), ZZ::from(c)));
+        assert!(g.is_reduced());
+        assert_eq!(g, T::generator());
+    }
+
+    check::<SmallClassConfig>(13, 3, "19231");
+    check::<MediumClassConfig>(2, 1, "35793917781300132272200147990708632007");
+    check::<EvenClassConfig>(3, 2, "24358038141974510935842793998");
+
+    // D = 1 mod 8, so 2 splits and g = (2, 1, (1 - D) / 8)
+    check::<TestClassConfig>(2, 1, "539711476982140706859637882975692282288066756989194874492237033176778308459479777245933013977975704674710211193616672535995425487878008837369087339195575000127537806943947442671878767270172719015960998382005552160810694796250777330619289307012267714570432169568087523461786589367499691166323373710709714142300603747871619932914559874936375891041773324830635425813007300866260261784441548985676004641297958096618812583397001322311917309431155616961442156869859253218558264429683744301203150367694381531205358849116811652362372446815098961696981388713971572930958328203977009905248736308154442169938909803962181012346765859740167907550143319332369014669973631784061152209698973207300391392756300593186970416273923127756562077631537486305446936217814449437241393157410917392762443085883201733283572995497951967769480922961085164929633991501206271067262003258373188657154122604704153664190737299317412422127352397367947241572994");
+
+    // GENERATOR_PRIME = 7
+    check::<FixedGeneratorClassConfig>(7, 3, "154203279137754487674182252278769223510876216282627106997782009479079516702708507784552289707993058478488631769604763581712978710822288239248310668341592857179296516269699269334822504934335062575988856680573014903088769941785936380176939802003505061305837762733739292417653311247857054618949535345917061183514458213677605695118445678553250254583363807094467264518002085961788646224126156853050287040370845170462517880970571806374833516980330176274697759105674072348159504122766784086058042962198394723201531099747660472103534984804313989056280396775420449408845236629707717115785353230901269191411117086846337432099075959925762259300040948380676861334278180509731757774199706630657254683644657312339134404649692322216160593609010710372984838919375556982068969473545976397932126595966629066652449427285129133648423120846024332837038283286058934590646286645249482473472606458486901046911639228376403549179243542105127783306570");
+    assert_eq!(FixedGeneratorClassConfig::generator(), ClassGroup::prime_form(ZZ::from(7u64)).unwrap());
+}
+
+// 61-bit discriminant for baby-step giant-step
+class_config!(BsgsClassConfig, -ZZ::from("1152921504606847067"), ZZ::from(23170i64));
+
+// Generic over the coefficient type, as ClassGroup::order is
+fn order_naive<T: ClassConfig>(f: &ClassGroup<T>) -> u64 {
+    let mut g = f.clone();
+    let mut n = 1;
+    while !g.is_identity() {
+        g += f;
+        n += 1;
+    }
+    n
+}
+
+fn check_order<T: ClassConfig>(f: &ClassGroup<T>) -> T::Int {
+    let n = f.order(None);
+    assert!((f.clone() * &n).is_identity());
+    for (q, _) in factor(&n) {
+        let mut m = n.clone();
+        m.div_exact(&q);
+        assert!(!(f.clone() * &m).is_identity());
+    }
+    assert_eq!(f.order(Some(&(n.clone() * T::Int::from(360u64)))), n);
+    n
+}
+
+#[test]
+fn order_matches_naive() {
+    let rng = &mut test_rng();
+    let h = ZZ::from(class_number_naive::<SmallClassConfig>());
+
+    assert_eq!(ClassGroup::<SmallClassConfig>::zero().order(None), ZZ::from(1u64));
+    assert_eq!(ClassGroup::<SmallClassConfig>::zero().order(Some(&h)), ZZ::from(1u64));
+    for _ in 0..20 {
+        let f = ClassGroup::<SmallClassConfig>::rand(rng);
+        let n = f.order(None);
+        assert_eq!(n, ZZ::from(order_naive(&f)));
+        assert_eq!(f.order(Some(&h)), n);
+        assert!(h.is_divisible(&n));
+    }
+
+    for _ in 0..3 {
+        check_order(&ClassGroup::<BsgsClassConfig>::rand(rng));
+    }
+    check_order(&BsgsClassConfig::generator());
+}
+
+#[test]
+fn factor_matches_products() {
+    let cases: [&[(&str, u32)]; 5] = [
+        &[],
+        &[("2", 10), ("3", 1), ("65537", 2)],
+        &[("1000003", 1), ("2147483647", 1)],
+        &[("1000003", 2), ("2305843009213693951", 1)],
+        &[("3", 2), ("4294967311", 1), ("4294967357", 1), ("1152921504606847067", 1)],
+    ];
+    for case in cases {
+        let expected: Vec<(ZZ, u32)> = case.iter().map(|&(p, e)| (ZZ::from(p), e)).collect();
+        let n: ZZ = expected.iter().map(|(p, e)| p.pow(e)).product();
+        assert_eq!(factor(&n), expected);
+        assert_eq!(factor(&-n), expected);
+    }
+}
+
+#[test]
+fn class_number_matches_naive() {
+    assert_eq!(SmallClassConfig::class_number(), Ok(ZZ::from(class_number_naive::<SmallClassConfig>())));
+    assert_eq!(NonFundamentalClassConfig::class_number(), Ok(ZZ::from(2u64)));
+    for (d, h) in [(-3i64, 1u64), (-4, 1), (-23, 3), (-60, 2), (-1000003, class_number_naive::<SmallClassConfig>())] {
+        assert_eq!(class_number(&ZZ::from(d)), Ok(ZZ::from(h)));
+    }
+
+    // Just above the bound where class_number counts forms itself, including non-fundamental
+    // discriminants and a 2-rank of 3
+    for d in [-(1i64 << 25) - 3, -(1 << 25) - 4, -(1 << 26) - 7, -9 * 3000019, -4 * 3 * 5 * 7 * 11 * 13 * 3001] {
+        assert_eq!(class_number(&ZZ::from(d)), Ok(ZZ::from(class_number_count(d))), "D = {d}");
+    }
+}
+
+#[test]
+fn class_number_shanks_mestre() {
+    // Every element has order dividing h, and h is a multiple of the order
+    let h = BsgsClassConfig::class_number().unwrap();
+    let rng = &mut test_rng();
+    for _ in 0..5 {
+        let f = ClassGroup::<BsgsClassConfig>::rand(rng);
+        assert!((f.clone() * &h).is_identity());
+        assert!(h.is_divisible(&f.order(None)));
+    }
+    assert_eq!(class_number(&BsgsClassConfig::discriminant()), Ok(h));
+}
+
+// D = -4 * 3 * 5 * 7 * 11 * 13 * 17 * 19 * 23 * 29 * 47, class group of 2-rank 10 by genus theory, so
+// its exponent is at most h(D) / 512, too small to single out h(D) in the interval of class_number.
+// h(D) = 270336 is the one ClassGroupStructure finds with verify, also from larger factor bases.
+class_config!(HighRankClassConfig, ZZ::from(-4i64 * 3 * 5 * 7 * 11 * 13 * 17 * 19 * 23 * 29 * 47), ZZ::from(624i64));
+
+#[test]
+fn class_number_errors() {
+    for d in [ZZ::zero(), ZZ::from(5u64), ZZ::from(-(1i64 << 25) - 1), ZZ::from(-6i64)] {
+        assert_eq!(class_number(&d), Err(ClassNumberError::InvalidDiscriminant), "D = {d:?}");
+    }
+
+    // About h(D) / 500 = 2^56 multiples for the first prime form, past MAX_GIANT_STEPS giant steps
+    let d = -(ZZ::from(1u128 << 65).pow(&2) + ZZ::from(3u64));
+    assert_eq!(class_number(&d), Err(ClassNumberError::IntervalTooWide));
+
+    // ClassConfig::class_number falls back to ClassGroupStructure
+    assert_eq!(class_number(&HighRankClassConfig::discriminant()), Err(ClassNumberError::ExponentTooSmall));
+    let h = HighRankClassConfig::class_number().unwrap();
+    assert_eq!(h, ZZ::from(270336u64));
+    check_structure::<HighRankClassConfig>(&h, 10, false);
+}
+
+#[test]
+fn class_number_runs_concurrently() {
+    // Each call carries its own discriminant, so calls for other discriminants on other threads
+    // do not interfere
+    let ds = [-(1i64 << 25) - 3, -(1 << 25) - 4, -(1 << 26) - 7];
+    let hs: Vec<ZZ> = std::thread::scope(|scope| {
+        let calls: Vec<_> = ds.iter().map(|&d| scope.spawn(move || class_number(&ZZ::from(d)))).collect();
+        calls.into_iter().map(|call| call.join().unwrap().unwrap()).collect()
+    });
+    for (d, h) in ds.into_iter().zip(hs) {
+        assert_eq!(h, ZZ::from(class_number_count(d)), "D = {d}");
+    }
+}
+
+// D = -(2^100 + 15). No PARI or Magma value is at hand, the pinned h(D) is the one class_number and
+// ClassGroupStructure::compute agree on, the latter through relations in the factor base
+#[test]
+#[ignore = "about 10 s in release, much longer in debug builds"]
+fn class_number_100_bits() {
+    let d = -(ZZ::from(1u128 << 100) + ZZ::from(15u64));
+    assert_eq!(class_number(&d), Ok(ZZ::from(740918972169270u64)));
+}
+
+// D = -4 * 3 * 5 * 7 * 11 * 13 * 3001, class group of 2-rank 5 by genus theory
+class_config!(TwoRankClassConfig, ZZ::from(-4i64 * 3 * 5 * 7 * 11 * 13 * 3001), ZZ::from(115i64));
+
+fn check_structure<T: ClassConfig<Int = ZZ>>(h: &ZZ, two_rank: usize, verify: bool) {
+    let s = ClassGroupStructure::<T>::compute(verify).unwrap();
+    assert_eq!(&s.class_number(), h);
+    assert_eq!(s.invariants.iter().filter(|d| !d.is_odd()).count(), two_rank);
+    for (i, (d, g)) in s.invariants.iter().zip(&s.generators).enumerate() {
+        assert!(i == 0 || d.is_divisible(&s.invariants[i - 1]));
+        assert_eq!(&g.order(Some(h)), d);
+    }
+
+    let rng = &mut test_rng();
+    for _ in 0..5 {
+        let c: Vec<ZZ> = s.invariants.iter().map(|d| ZZ::from(rng.gen_range(0..d.value.to_u64().unwrap_or(u64::MAX)))).collect();
+        let f = s.element(&c);
+        assert_eq!(s.coordinates(&f).unwrap(), c);
+
+        // Coordinates are additive
+        let g = ClassGroup::<T>::rand(rng);
+        let (x, y) = (s.coordinates(&(f.clone() + &g)).unwrap(), s.coordinates(&g).unwrap());
+        for (((x, y), c), d) in x.into_iter().zip(y).zip(&c).zip(&s.invariants) {
+            assert!((x - y - c).rem_floor(d).is_zero());
+        }
+        assert_eq!(s.element(&s.coordinates(&g).unwrap()), g);
+    }
+}
+
+#[test]
+fn class_group_structure() {
+    check_structure::<NonFundamentalClassConfig>(&ZZ::from(2u64), 1, true);
+    check_structure::<SmallClassConfig>(&ZZ::from(class_number_naive::<SmallClassConfig>()), 0, true);
+    check_structure::<TwoRankClassConfig>(&TwoRankClassConfig::class_number().unwrap(), 5, true);
+    check_structure::<BsgsClassConfig>(&BsgsClassConfig::class_number().unwrap(), 0, true);
+
+    assert_eq!(
+        ClassGroupStructure::<SmallClassConfig>::compute_with_bound(1 << 31, false).unwrap_err(),
+        StructureError::BoundTooLarge
+    );
+    assert_eq!(
+        ClassGroupStructure::<SmallClassConfig>::compute_with_bound(1, false).unwrap_err(),
+        StructureError::EmptyFactorBase
+    );
+}
+
+// D = -(2^110 + 27), prime and 3 mod 4, so the 2-rank is 0. h(D) = 8881823495136537 from class_number,
+// ClassGroupStructure finds Z/3 + Z/2960607831712179
+class_config!(
+    StructureClassConfig,
+    -(ZZ::from(1u128 << 110) + ZZ::from(27u64)),
+    ZZ::from(1u64 << 27)
+);
+
+#[test]
+#[ignore = "about a minute in release, much longer in debug builds"]
+fn class_group_structure_110_bits() {
+    let h = class_number(&StructureClassConfig::discriminant()).unwrap();
+    assert_eq!(h, ZZ::from(8881823495136537u64));
+    check_structure::<StructureClassConfig>(&h, 0, true);
+}
+
+// D = -(2^140 + 207), prime and 3 mod 4. Out of reach of class_number, the pinned h(D) is the order of
+// the cyclic group ClassGroupStructure finds, which check_structure confirms for its generator
+class_config!(
+    LargeStructureClassConfig,
+    -(ZZ::from(1u128 << 70).pow(&2) + ZZ::from(207u64)),
+    ZZ::from(1u64 << 35)
+);
+
+#[test]
+#[ignore = "about 3 s in release, a minute in debug builds"]
+fn class_group_structure_140_bits() {
+    check_structure::<LargeStructureClassConfig>(&ZZ::from(399517737755846966137u128), 0, true);
+}
+
+// D = -(2^160 + 7), prime and 3 mod 4, pinned h(D) as for 140 bits. Checked without verify, which would
+// take about three times as long
+class_config!(
+    Structure160ClassConfig,
+    -(ZZ::from(1u128 << 80).pow(&2) + ZZ::from(7u64)),
+    ZZ::from(1u64 << 40)
+);
+
+#[test]
+fn class_group_structure_160_bits() {
+    check_structure::<Structure160ClassConfig>(&ZZ::from(1137177983623734548053575u128), 0, false);
+}
+
+// D = -(2^200 + 235), prime and 3 mod 4, pinned h(D) as for 140 bits
+class_config!(
+    Structure200ClassConfig,
+    -(ZZ::from(1u128 << 100).pow(&2) + ZZ::from(235u64)),
+    ZZ::from(1u64 << 50)
+);
+
+#[test]
+#[ignore = "about 3.5 minutes in release"]
+fn class_group_structure_200_bits() {
+    check_structure::<Structure200ClassConfig>(&ZZ::from(386583874683420302836869610523u128), 0, true);
+}
@@ -0,0 +1,117 @@
+use ark_guo::{
+    class::{config::TestClassConfig, ClassGroup},
+    integer::{ZZ, MAX_SERIALIZED_BYTES},
+    Integer,
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalDeserializeWithFlags, CanonicalSerialize, EmptyFlags};
+use ark_std::{rand::Rng, test_rng};
+
+fn floor_div(x: i128, y: i128) -> i128 {
+    let q = x / y;
+    if x % y != 0 && (x < 0) != (y < 0) { q - 1 } else { q }
+}
+
+fn ceil_div(x: i128, y: i128) -> i128 {
+    let q = x / y;
+    if x % y != 0 && (x < 0) == (y < 0) { q + 1 } else { q }
+}
+
+fn gcd(x: i128, y: i128) -> i128 {
+    if y == 0 { x.abs() } else { gcd(y, x % y) }
+}
+
+// Integer methods of I against i128 arithmetic, on i64 inputs so that nothing overflows
+fn check_integer<I: Integer>(x: i64, y: i64) {
+    let (xi, yi) = (x as i128, y as i128);
+    let (a, b) = (I::from(x), I::from(y));
+    let int = I::from;
+
+    assert_eq!(a.is_odd(), xi % 2 != 0);
+    assert_eq!(a.abs(), int(xi.abs()));
+    assert_eq!(a.significant_bits(), 128 - xi.unsigned_abs().leading_zeros());
+    assert_eq!(a.pow(&2), int(xi * xi));
+    for i in [0, 1, 17, 63, 64, 100] {
+        assert_eq!(a.get_bit(i), (xi >> i.min(127)) & 1 == 1, "bit {i} of {x}");
+        assert_eq!(a.clone() >> i, int(xi >> i.min(127)), "{x} >> {i}");
+        assert_eq!(a.shr_to_i64_wrapping(i), (xi / (1i128 << i.min(126))) as i64, "{x} / 2^{i}");
+    }
+
+    if y != 0 {
+        assert_eq!(a.modulus(&b), int(xi % yi));
+        assert_eq!(a.rem_floor(&b), int(xi - floor_div(xi, yi) * yi));
+        let mut r = a.clone();
+        r.rem_floor_mut(&b);
+        assert_eq!(r, a.rem_floor(&b));
+        assert_eq!(a.is_divisible(&b), xi % yi == 0);
+
+        let (mut q, mut r) = (a.clone(), b.clone());
+        q.div_rem_floor_mut(&mut r);
+        assert_eq!((q, r), (int(floor_div(xi, yi)), int(xi - floor_div(xi, yi) * yi)));
+        let (mut q, mut r) = (a.clone(), b.clone());
+        q.div_rem_ceil_mut(&mut r);
+        assert_eq!((q, r), (int(ceil_div(xi, yi)), int(xi - ceil_div(xi, yi) * yi)));
+
+        let mut q = I::from(xi * yi);
+        q.div_exact(&b);
+        assert_eq!(q, a);
+    }
+
+    // g = s x + t y
+    let (mut g, mut s, mut t) = (a.clone(), b.clone(), I::zero());
+    g.extended_gcd_mut(&mut s, &mut t);
+    assert_eq!(g, int(gcd(xi, yi)));
+    let mut st = s * a.clone();
+    st.add_mul(&t, &b);
+    assert_eq!(st, g);
+
+    let mut r = I::one();
+    r.assign_i64(x);
+    assert_eq!(r, a);
+    r.assign_mul(&a, &b);
+    assert_eq!(r, int(xi * yi));
+    r.add_mul(&a, &a);
+    assert_eq!(r, int(xi * yi + xi * xi));
+    r.sub_mul(&b, &b);
+    assert_eq!(r, int(xi * yi + xi * xi - yi * yi));
+}
+
+#[test]
+fn integer_trait_matches_i128() {
+    let rng = &mut test_rng();
+    let edge = [0, 1, -1, 2, -2, 7, -7, i64::MAX, i64::MIN + 1];
+    for &x in &edge {
+        for &y in &edge {
+            check_integer::<ZZ>(x, y);
+        }
+    }
+    for _ in 0..1000 {
+        let bits = rng.gen_range(1..64);
+        let x = rng.gen_range(-(1i64 << (bits - 1))..1i64 << (bits - 1));
+        let y = rng.gen_range(-(1i64 << (bits - 1))..1i64 << (bits - 1));
+        check_integer::<ZZ>(x, y);
+    }
+}
+
+// Radix, length prefix and digits of a serialized ZZ, with the length prefix replaced
+fn forged(len: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    ZZ::from(12345).serialize_compressed(&mut bytes).unwrap();
+    bytes[4..12].copy_from_slice(&len.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn deserialization_rejects_huge_length_prefix() {
+    let x = ZZ::from(1u128 << 100) * ZZ::from(-3);
+    let mut bytes = Vec::new();
+    x.serialize_compressed(&mut bytes).unwrap();
+    assert_eq!(ZZ::deserialize_compressed(&bytes[..]).unwrap(), x);
+
+    // Fails on the size limit before allocating
+    for len in [u64::MAX, u64::MAX / 2, MAX_SERIALIZED_BYTES + 1] {
+        let bytes = forged(len);
+        assert!(ZZ::deserialize_compressed(&bytes[..]).is_err());
+        assert!(ZZ::deserialize_with_flags::<_, EmptyFlags>(&bytes[..]).is_err());
+        assert!(ClassGroup::<TestClassConfig>::deserialize_compressed(&bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+use ark_guo::shake::Shake256;
+
+fn shake256(data: &[u8], len: usize) -> String {
+    let mut xof = Shake256::new();
+    xof.update(data);
+    let mut out = vec![0u8; len];
+    xof.squeeze(&mut out);
+    out.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+#[test]
+fn shake256_known_answers() {
+    assert_eq!(
+        shake256(b"", 64),
+        "46b9dd2b0ba88d13233b3feb743eeb243fcd52ea62b81b82b50c27646ed5762f\
+         d75dc4ddd8c0f200cb05019d67b592f6fc821c49479ab48640292eacb3b7c4be"
+    );
+    assert_eq!(
+        shake256(b"abc", 64),
+        "483366601360a8771c6863080cc4114d8db44530f8f1e1ee4f94ea37e78b5739\
+         d5a15bef186a5386c75744c0527e1faa9f8726e462a12a4feb06bd8801e751e4"
+    );
+    // Several rate blocks absorbed
+    assert_eq!(
+        shake256(&[0xa3; 200], 32),
+        "cd8a920ed141aa0407a22d59288652e9d9f1a7ee0c1e7c1ca699424da84a904d"
+    );
+    // Several rate blocks squeezed
+    let data: Vec<u8> = (0..3).flat_map(|_| 0..=255u8).collect();
+    assert!(shake256(&data, 300).ends_with("c068159a1ca7f3667f2e0729df9f4b19e0f2bb2035a07d8b5db20bcbade3ac92"));
+}
+
+#[test]
+fn shake256_incremental_matches_one_shot() {
+    let data: Vec<u8> = (0..1000u32).map(|i| (i * 7 + 3) as u8).collect();
+    let expected = shake256(&data, 500);
+
+    let mut xof = Shake256::new();
+    for chunk in data.chunks(37) {
+        xof.update(chunk);
+    }
+    let mut out = vec![0u8; 500];
+    for chunk in out.chunks_mut(41) {
+        xof.squeeze(chunk);
+    }
+    let out: String = out.iter().map(|x| format!("{x:02x}")).collect();
+    assert_eq!(out, expected);
+}
@@ -0,0 +1,128 @@
+// Checks that zeroization overwrites GMP limb buffers before they are released, by inspecting
+// every watched buffer in the GMP realloc and free functions.
+// This replaces the process-wide GMP memory functions, so it lives in its own test binary.
+use std::{
+    ffi::c_void,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+use ark_guo::{
+    class::{
+        config::{ClassConfig, TestClassConfig},
+        context::ClassGroupContext,
+        ClassGroup,
+    },
+    integer::ZZ,
+    secret::SecretZZ,
+    Integer,
+};
+use gmp_mpfr_sys::gmp;
+use ark_std::Zero;
+use rug::Assign;
+use zeroize::Zeroize;
+
+type CG = ClassGroup<TestClassConfig>;
+
+// Buffers to inspect when released, and how many of them were released with non-zero bytes
+static WATCHED: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+static DIRTY: AtomicUsize = AtomicUsize::new(0);
+static RELEASED: AtomicUsize = AtomicUsize::new(0);
+static DEFAULTS: OnceLock<(gmp::allocate_function, gmp::reallocate_function, gmp::free_function)> = OnceLock::new();
+
+fn inspect(ptr: *mut c_void, size: usize) {
+    let mut watched = WATCHED.lock().unwrap();
+    if let Some(i) = watched.iter().position(|&p| p == ptr as usize) {
+        watched.swap_remove(i);
+        RELEASED.fetch_add(1, Ordering::SeqCst);
+        let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, size) };
+        if bytes.iter().any(|&b| b != 0) {
+            DIRTY.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+extern "C" fn inspecting_alloc(size: usize) -> *mut c_void {
+    DEFAULTS.get().unwrap().0.unwrap()(size)
+}
+
+unsafe extern "C" fn inspecting_realloc(ptr: *mut c_void, old_size: usize, new_size: usize) -> *mut c_void {
+    inspect(ptr, old_size);
+    unsafe { DEFAULTS.get().unwrap().1.unwrap()(ptr, old_size, new_size) }
+}
+
+unsafe extern "C" fn inspecting_free(ptr: *mut c_void, size: usize) {
+    inspect(ptr, size);
+    unsafe { DEFAULTS.get().unwrap().2.unwrap()(ptr, size) }
+}
+
+fn install() {
+    DEFAULTS.get_or_init(|| {
+        let (mut alloc, mut realloc, mut free) = (None, None, None);
+        unsafe {
+            gmp::get_memory_functions(&mut alloc, &mut realloc, &mut free);
+            gmp::set_memory_functions(Some(inspecting_alloc), Some(inspecting_realloc), Some(inspecting_free));
+        }
+        (alloc, realloc, free)
+    });
+}
+
+fn watch(x: &ZZ) {
+    let d = unsafe { (*x.value.as_raw()).d.as_ptr() };
+    WATCHED.lock().unwrap().push(d as usize);
+}
+
+// Tests share the counters, so they run one after the other in a single test
+#[test]
+fn zeroize_scrubs_limbs() {
+    install();
+
+    // A large value overwritten by a small one keeps its old high limbs around
+    let mut x = ZZ::from(1) + ZZ::from(3).pow(&5000);
+    watch(&x);
+    x.value.assign(5);
+    x.zeroize();
+    assert!(x.is_zero());
+    assert_eq!(RELEASED.swap(0, Ordering::SeqCst), 1);
+
+    // Forms and contexts
+    let mut f = CG::new_unchecked(ZZ::from(7).pow(&3000), ZZ::zero(), ZZ::from(11).pow(&3000));
+    watch(&f.a);
+    watch(&f.c);
+    f.zeroize();
+    assert_eq!(RELEASED.swap(0, Ordering::SeqCst), 2);
+
+    // A real composition and squaring leave intermediate values in the temporaries of the context
+    let mut ctx = ClassGroupContext::<TestClassConfig>::new();
+    let g = TestClassConfig::generator() * ZZ::from(0xdead_beef_u64);
+    let (mut r, mut t) = (CG::default(), CG::default());
+    CG::nucomp_into(&mut ctx, &mut t, &g, &TestClassConfig::generator());
+    CG::nudupl_into(&mut ctx, &mut r, &t);
+    let held: Vec<&ZZ> = ctx.temporaries().into_iter().filter(|x| x.value.capacity() > 0).collect();
+    assert!(held.len() > 20);
+    assert!(held.iter().any(|x| x.significant_bits() > 1000));
+    let n = held.len();
+    held.into_iter().for_each(watch);
+    ctx.zeroize();
+    assert_eq!(RELEASED.swap(0, Ordering::SeqCst), n);
+    assert!(ctx.temporaries().iter().all(|x| x.is_zero()));
+    assert_eq!(DIRTY.load(Ordering::SeqCst), 0);
+
+    // Secrets are scrubbed on drop
+    let s = SecretZZ::new(ZZ::from(13).pow(&4000) + TestClassConfig::discriminant());
+    watch(&s);
+    assert_eq!(format!("{:?}", s), "Secret(..)");
+    drop(s);
+    assert_eq!(RELEASED.swap(0, Ordering::SeqCst), 1);
+
+    assert_eq!(DIRTY.load(Ordering::SeqCst), 0);
+
+    // Sanity check of the inspection: a plain drop leaves the limbs in place
+    let y = ZZ::from(17).pow(&3000);
+    watch(&y);
+    drop(y);
+    assert_eq!(RELEASED.swap(0, Ordering::SeqCst), 1);
+    assert_eq!(DIRTY.load(Ordering::SeqCst), 1);
+}